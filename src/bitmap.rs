@@ -0,0 +1,126 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::ops::Range;
+
+/// An exFAT Allocation Bitmap: bit `n` covers cluster `n + 2`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AllocationBitmap {
+    pub index: u8, // BitmapFlags bit 0 (0 = first bitmap, 1 = second on TexFAT)
+    pub cluster_count: u32,
+    #[serde(skip)]
+    bytes: Vec<u8>,
+}
+
+impl AllocationBitmap {
+    pub fn new(index: u8, cluster_count: u32, bytes: Vec<u8>) -> Self {
+        Self {
+            index,
+            cluster_count,
+            bytes,
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// One past the last valid cluster number.
+    #[inline]
+    fn end(&self) -> u32 {
+        self.cluster_count.saturating_add(2)
+    }
+
+    /// Clusters outside the heap (0, 1, past the end) or past a truncated bitmap
+    /// are reported as allocated, so nothing treats them as free space.
+    pub fn is_allocated(&self, cluster: u32) -> bool {
+        if cluster < 2 || cluster >= self.end() {
+            return true;
+        }
+        let idx = (cluster - 2) as usize;
+        self.bytes
+            .get(idx / 8)
+            .is_none_or(|b| b & (1u8 << (idx % 8)) != 0)
+    }
+
+    /// Runs of allocated clusters, as `first..end` cluster ranges in ascending order.
+    pub fn allocated_runs(&self) -> BitmapRuns<'_> {
+        BitmapRuns {
+            bitmap: self,
+            next: 2,
+            allocated: true,
+        }
+    }
+
+    /// Runs of unallocated clusters, as `first..end` cluster ranges in ascending order.
+    pub fn unallocated_runs(&self) -> BitmapRuns<'_> {
+        BitmapRuns {
+            bitmap: self,
+            next: 2,
+            allocated: false,
+        }
+    }
+
+    pub fn used_clusters(&self) -> u64 {
+        self.allocated_runs()
+            .map(|r| (r.end - r.start) as u64)
+            .sum()
+    }
+
+    pub fn free_clusters(&self) -> u64 {
+        self.cluster_count as u64 - self.used_clusters()
+    }
+
+    pub fn to_json(&self) -> Value {
+        json!({
+            "index": self.index,
+            "cluster_count": self.cluster_count,
+            "used_clusters": self.used_clusters(),
+            "free_clusters": self.free_clusters(),
+        })
+    }
+}
+
+/// Iterator over maximal runs of clusters sharing one allocation state.
+pub struct BitmapRuns<'a> {
+    bitmap: &'a AllocationBitmap,
+    next: u32,
+    allocated: bool,
+}
+
+impl BitmapRuns<'_> {
+    /// First cluster at or after `c` whose state is `state`, skipping whole bytes when possible.
+    fn seek(&self, mut c: u32, state: bool) -> u32 {
+        let end = self.bitmap.end();
+        let skip = if state { 0x00 } else { 0xFF };
+        while c < end {
+            let idx = (c - 2) as usize;
+            if idx.is_multiple_of(8)
+                && let Some(&b) = self.bitmap.bytes.get(idx / 8)
+                && b == skip
+            {
+                c = c.saturating_add(8).min(end);
+                continue;
+            }
+            if self.bitmap.is_allocated(c) == state {
+                return c;
+            }
+            c += 1;
+        }
+        end
+    }
+}
+
+impl Iterator for BitmapRuns<'_> {
+    type Item = Range<u32>;
+
+    fn next(&mut self) -> Option<Range<u32>> {
+        let start = self.seek(self.next, self.allocated);
+        if start >= self.bitmap.end() {
+            self.next = start;
+            return None;
+        }
+        let end = self.seek(start, !self.allocated);
+        self.next = end;
+        Some(start..end)
+    }
+}
@@ -0,0 +1,232 @@
+use crate::bpb::ExtendedBootSector;
+use log::warn;
+use md5::Md5;
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+
+/// How a known boot code image is recognised.
+#[derive(Debug, Clone)]
+pub enum BootCodeMatcher {
+    /// Every byte of the boot code has this value.
+    Fill(u8),
+    /// The boot code contains this byte string (e.g. its error messages). A heuristic:
+    /// code patched around the string still matches, so the match stays unverified.
+    Contains(&'static [u8]),
+    /// Exact SHA-256 of the 390-byte boot code (hex).
+    Sha256(Cow<'static, str>),
+}
+
+impl BootCodeMatcher {
+    /// The match covers every byte of the boot code.
+    pub fn is_exact(&self) -> bool {
+        !matches!(self, BootCodeMatcher::Contains(_))
+    }
+}
+
+/// A boot code image written by a known formatter.
+#[derive(Debug, Clone)]
+pub struct KnownBootCode {
+    pub name: Cow<'static, str>,
+    pub matcher: BootCodeMatcher,
+}
+
+/// Built-in reference table, exact matches first.
+///
+/// Coverage is limited: no verified hash of Windows or macOS newfs_exfat boot code
+/// could be sourced, so Windows is only recognised by its message strings (an
+/// unverified match) and macOS not at all. Hashes taken from reference media are
+/// loaded with [`parse_known_boot_code`] and passed to [`BootCodeReport::analyze_with`].
+pub const KNOWN_BOOT_CODE: &[KnownBootCode] = &[
+    KnownBootCode {
+        name: Cow::Borrowed("mkfs.exfat (exfatprogs, HLT fill)"),
+        matcher: BootCodeMatcher::Sha256(Cow::Borrowed(
+            "8884b195d7c21b147ca818abb9acdda91ceb70b7f982cea1067122911e668894",
+        )),
+    },
+    KnownBootCode {
+        name: Cow::Borrowed("Zero-filled (embedded / camera firmware formatters)"),
+        matcher: BootCodeMatcher::Fill(0x00),
+    },
+    KnownBootCode {
+        name: Cow::Borrowed("Windows-like (message strings only)"),
+        matcher: BootCodeMatcher::Contains(b"Press any key to restart"),
+    },
+];
+
+/// What the built-in table can and cannot identify, shown with every report.
+pub const BUILTIN_COVERAGE: &str = "built-in exact references: mkfs.exfat (exfatprogs), zero fill \
+(camera / embedded firmware); no verified Windows or macOS newfs_exfat hash \
+(Windows is matched by message strings only, macOS is not recognised)";
+
+/// Parse a reference list of boot code hashes, one `<sha256> <name>` per line
+/// (`#` starts a comment). Malformed lines are skipped with a warning.
+pub fn parse_known_boot_code(text: &str) -> Vec<KnownBootCode> {
+    let mut out = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (hash, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        if hash.len() != 64 || !hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            warn!("boot code reference line {}: not a SHA-256 hash", n + 1);
+            continue;
+        }
+        let name = name.trim();
+        out.push(KnownBootCode {
+            name: Cow::Owned(if name.is_empty() {
+                hash.to_string()
+            } else {
+                name.to_string()
+            }),
+            matcher: BootCodeMatcher::Sha256(Cow::Owned(hash.to_ascii_lowercase())),
+        });
+    }
+    out
+}
+
+fn md5_hex(b: &[u8]) -> String {
+    format!("{:x}", Md5::digest(b))
+}
+
+fn sha256_hex(b: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(b))
+}
+
+/// Hashes of the code area of one extended boot sector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtendedBootCode {
+    pub sector: u8,
+    pub all_zero: bool,
+    pub md5: String,
+    pub sha256: String,
+}
+
+/// Boot code triage: hashes, known-formatter match and extended boot sector contents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootCodeReport {
+    pub length: usize,
+    pub nonzero_bytes: usize,
+    pub md5: String,
+    pub sha256: String,
+    pub known_match: Option<String>,
+    /// The match covers the whole boot code (hash or fill), not just a string in it.
+    pub known_verified: bool,
+    pub extended: Vec<ExtendedBootCode>,
+    /// What the reference table covers, and how many exact references were used.
+    pub reference_coverage: String,
+    /// Boot code is neither blank nor known exactly, or an extended boot sector carries code.
+    pub suspicious: bool,
+}
+
+impl BootCodeReport {
+    pub fn analyze(boot_code: &[u8], region: &[u8], bytes_per_sector: usize) -> Self {
+        Self::analyze_with(boot_code, region, bytes_per_sector, KNOWN_BOOT_CODE)
+    }
+
+    /// `boot_code` is the 390-byte area of sector 0, `region` the boot region it came from.
+    pub fn analyze_with(
+        boot_code: &[u8],
+        region: &[u8],
+        bytes_per_sector: usize,
+        known: &[KnownBootCode],
+    ) -> Self {
+        let sha256 = sha256_hex(boot_code);
+        // exact matches win over string heuristics, whatever the table order
+        let hit = |k: &&KnownBootCode| match &k.matcher {
+            BootCodeMatcher::Fill(v) => boot_code.iter().all(|b| b == v),
+            BootCodeMatcher::Contains(pat) => boot_code.windows(pat.len()).any(|w| w == *pat),
+            BootCodeMatcher::Sha256(h) => h.eq_ignore_ascii_case(&sha256),
+        };
+        let found = known
+            .iter()
+            .filter(|k| k.matcher.is_exact())
+            .find(hit)
+            .or_else(|| known.iter().find(hit));
+        let known_match = found.map(|k| k.name.to_string());
+        let known_verified = found.is_some_and(|k| k.matcher.is_exact());
+
+        let extended: Vec<ExtendedBootCode> =
+            ExtendedBootSector::all_from_region(region, bytes_per_sector)
+                .into_iter()
+                .map(|e| {
+                    let start = e.sector as usize * bytes_per_sector;
+                    let code = &region[start..start + bytes_per_sector - 4];
+                    ExtendedBootCode {
+                        sector: e.sector,
+                        all_zero: e.code_is_zero,
+                        md5: md5_hex(code),
+                        sha256: sha256_hex(code),
+                    }
+                })
+                .collect();
+
+        let nonzero_bytes = boot_code.iter().filter(|&&b| b != 0).count();
+        let suspicious =
+            (nonzero_bytes > 0 && !known_verified) || extended.iter().any(|e| !e.all_zero);
+
+        Self {
+            length: boot_code.len(),
+            nonzero_bytes,
+            md5: md5_hex(boot_code),
+            sha256,
+            known_match,
+            known_verified,
+            reference_coverage: format!(
+                "{}; {} exact references in use",
+                BUILTIN_COVERAGE,
+                known.iter().filter(|k| k.matcher.is_exact()).count()
+            ),
+            extended,
+            suspicious,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for BootCodeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut t = Table::new();
+        t.add_row(Row::new(vec![
+            Cell::new("Boot code"),
+            Cell::new(&format!(
+                "{} bytes, {} non-zero",
+                self.length, self.nonzero_bytes
+            )),
+        ]));
+        t.add_row(Row::new(vec![Cell::new("MD5"), Cell::new(&self.md5)]));
+        t.add_row(Row::new(vec![
+            Cell::new("SHA-256"),
+            Cell::new(&self.sha256),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Known formatter"),
+            Cell::new(&match (&self.known_match, self.known_verified) {
+                (Some(name), true) => name.clone(),
+                (Some(name), false) => format!("{} (unverified)", name),
+                (None, _) => "unknown".to_string(),
+            }),
+        ]));
+        for e in self.extended.iter().filter(|e| !e.all_zero) {
+            t.add_row(Row::new(vec![
+                Cell::new(&format!("Extended boot sector {}", e.sector)),
+                Cell::new(&format!("code present, SHA-256 {}", e.sha256)),
+            ]));
+        }
+        t.add_row(Row::new(vec![
+            Cell::new("Reference coverage"),
+            Cell::new(&self.reference_coverage.replace("; ", "\n")),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Suspicious"),
+            Cell::new(&self.suspicious.to_string()),
+        ]));
+        write!(f, "{}", t)
+    }
+}
@@ -1,29 +1,241 @@
-use log::debug;
+use log::{debug, warn};
 use prettytable::{Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
+use sha2::{Digest, Sha256};
+
+/// Number of sectors in a boot region (main: 0..=11, backup: 12..=23).
+pub const BOOT_REGION_SECTORS: u64 = 12;
+
+/// exFAT boot checksum over sectors 0..=10 of a boot region.
+/// VolumeFlags (0x6A..0x6B) and PercentInUse (0x70) are excluded, as they change at runtime.
+pub fn boot_checksum(region: &[u8], bytes_per_sector: usize) -> u32 {
+    let len = (bytes_per_sector * 11).min(region.len());
+    let mut sum = 0u32;
+    for (i, &b) in region[..len].iter().enumerate() {
+        if i == 0x6A || i == 0x6B || i == 0x70 {
+            continue;
+        }
+        sum = sum.rotate_right(1).wrapping_add(b as u32);
+    }
+    sum
+}
+
+/// Result of checking the boot checksum against the repeated values of sector 11.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootChecksumVerdict {
+    pub computed: u32,
+    pub stored: u32, // first u32 of sector 11
+    pub repeats: usize,
+    pub mismatched_repeats: usize,
+    pub valid: bool,
+}
+
+impl BootChecksumVerdict {
+    /// `region` must hold the 12 sectors of a boot region.
+    pub fn verify(region: &[u8], bytes_per_sector: usize) -> Result<Self, String> {
+        let need = bytes_per_sector * BOOT_REGION_SECTORS as usize;
+        if region.len() < need {
+            return Err(format!(
+                "Boot region too short: {} < {} bytes",
+                region.len(),
+                need
+            ));
+        }
+        let computed = boot_checksum(region, bytes_per_sector);
+        let sector11 = &region[bytes_per_sector * 11..need];
+        let values: Vec<u32> = sector11
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect();
+        let mismatched_repeats = values.iter().filter(|&&v| v != computed).count();
+        if mismatched_repeats > 0 {
+            warn!(
+                "Boot checksum mismatch: computed=0x{:08X}, {} of {} stored values differ",
+                computed,
+                mismatched_repeats,
+                values.len()
+            );
+        }
+        Ok(Self {
+            computed,
+            stored: values.first().copied().unwrap_or(0),
+            repeats: values.len(),
+            mismatched_repeats,
+            valid: mismatched_repeats == 0,
+        })
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for BootChecksumVerdict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut t = Table::new();
+        t.add_row(Row::new(vec![
+            Cell::new("Boot checksum (computed)"),
+            Cell::new(&format!("0x{:08X}", self.computed)),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Boot checksum (stored)"),
+            Cell::new(&format!("0x{:08X}", self.stored)),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Mismatched repeats"),
+            Cell::new(&format!("{}/{}", self.mismatched_repeats, self.repeats)),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Verdict"),
+            Cell::new(if self.valid {
+                "OK"
+            } else {
+                "MISMATCH (boot region modified)"
+            }),
+        ]));
+        write!(f, "{}", t)
+    }
+}
+
+/// Decoded VolumeFlags (0x6A).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VolumeFlags {
+    pub active_fat: u8,      // bit 0: 0 = first FAT/bitmap, 1 = second
+    pub volume_dirty: bool,  // bit 1
+    pub media_failure: bool, // bit 2
+    pub clear_to_zero: bool, // bit 3
+    pub reserved: u16,       // bits 4..15, must be zero
+}
+
+impl VolumeFlags {
+    pub fn from_raw(v: u16) -> Self {
+        Self {
+            active_fat: (v & 0x0001) as u8,
+            volume_dirty: (v & 0x0002) != 0,
+            media_failure: (v & 0x0004) != 0,
+            clear_to_zero: (v & 0x0008) != 0,
+            reserved: v & 0xFFF0,
+        }
+    }
+}
+
+impl std::fmt::Display for VolumeFlags {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = vec![format!("ActiveFat={}", self.active_fat)];
+        if self.volume_dirty {
+            parts.push("VolumeDirty".into());
+        }
+        if self.media_failure {
+            parts.push("MediaFailure".into());
+        }
+        if self.clear_to_zero {
+            parts.push("ClearToZero".into());
+        }
+        if self.reserved != 0 {
+            parts.push(format!("Reserved=0x{:04X}", self.reserved));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+/// PercentInUse (0x70) compared with the usage counted in the allocation bitmap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PercentInUseCheck {
+    pub reported: u8, // 0xFF = not available
+    pub actual: u8,
+    pub used_clusters: u64,
+    pub cluster_count: u32,
+    pub consistent: bool,
+}
+
+impl PercentInUseCheck {
+    pub fn new(reported: u8, used_clusters: u64, cluster_count: u32) -> Self {
+        // the spec rounds down
+        let actual = (used_clusters * 100)
+            .checked_div(cluster_count as u64)
+            .unwrap_or(0)
+            .min(100) as u8;
+        Self {
+            reported,
+            actual,
+            used_clusters,
+            cluster_count,
+            consistent: reported == 0xFF || reported == actual,
+        }
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for PercentInUseCheck {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut t = Table::new();
+        let reported = if self.reported == 0xFF {
+            "n/a (0xFF)".to_string()
+        } else {
+            format!("{}%", self.reported)
+        };
+        t.add_row(Row::new(vec![
+            Cell::new("PercentInUse (boot sector)"),
+            Cell::new(&reported),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("PercentInUse (bitmap)"),
+            Cell::new(&format!(
+                "{}% ({}/{} clusters)",
+                self.actual, self.used_clusters, self.cluster_count
+            )),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Consistent"),
+            Cell::new(&self.consistent.to_string()),
+        ]));
+        write!(f, "{}", t)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BootSector {
-    pub oem_name: [u8; 8],        // 0x03 .. 0x0A (not critical for exFAT but kept)
-    pub partition_offset: u64,    // 0x40
-    pub volume_length: u64,       // 0x48 (in sectors)
-    pub fat_offset: u32,          // 0x50 (in sectors from volume start)
-    pub fat_length: u32,          // 0x54 (in sectors)
-    pub cluster_heap_offset: u32, // 0x58 (in sectors)
-    pub cluster_count: u32,       // 0x5C
-    pub root_dir_first_cluster: u32, // 0x60
-    pub volume_serial: u32,       // 0x64
-    pub fs_revision: u16,         // 0x68
-    pub volume_flags: u16,        // 0x6A
-    pub bytes_per_sector_shift: u8, // 0x6C (2^n)
+    pub jump_boot: [u8; 3],            // 0x00 .. 0x02 (EB 76 90)
+    pub oem_name: [u8; 8],             // 0x03 .. 0x0A (not critical for exFAT but kept)
+    pub must_be_zero_nonzero: usize,   // 0x0B .. 0x3F, count of non-zero bytes
+    pub partition_offset: u64,         // 0x40
+    pub volume_length: u64,            // 0x48 (in sectors)
+    pub fat_offset: u32,               // 0x50 (in sectors from volume start)
+    pub fat_length: u32,               // 0x54 (in sectors)
+    pub cluster_heap_offset: u32,      // 0x58 (in sectors)
+    pub cluster_count: u32,            // 0x5C
+    pub root_dir_first_cluster: u32,   // 0x60
+    pub volume_serial: u32,            // 0x64
+    pub fs_revision: u16,              // 0x68
+    pub volume_flags: u16,             // 0x6A
+    pub bytes_per_sector_shift: u8,    // 0x6C (2^n)
     pub sectors_per_cluster_shift: u8, // 0x6D (2^n)
-    pub num_fats: u8,             // 0x6E
-    pub drive_select: u8,         // 0x6F
+    pub num_fats: u8,                  // 0x6E
+    pub drive_select: u8,              // 0x6F
+    pub percent_in_use: u8,            // 0x70 (0xFF = not available)
+    #[serde(skip)]
+    pub must_be_zero: Vec<u8>, // 0x0B .. 0x3F
+    #[serde(skip)]
+    pub boot_code: Vec<u8>, // 0x78 .. 0x1FD (390 bytes)
 }
 
 impl BootSector {
     pub fn from_bytes(bs: &[u8]) -> Result<Self, String> {
+        Self::parse(bs, true).map(|(me, _)| me)
+    }
+
+    /// Parse without rejecting spec violations: each one is returned as a warning instead.
+    /// Sector and cluster shifts out of range are reset to 9 and 0 so the geometry helpers
+    /// stay usable; the caller is expected to infer the real values from the volume.
+    pub fn from_bytes_lenient(bs: &[u8]) -> Result<(Self, Vec<String>), String> {
+        Self::parse(bs, false)
+    }
+
+    fn parse(bs: &[u8], strict: bool) -> Result<(Self, Vec<String>), String> {
         if bs.len() < 512 {
             return Err(format!("Boot sector too short: {} < 512 bytes", bs.len()));
         }
@@ -48,17 +260,34 @@ impl BootSector {
                 })
         };
 
+        let mut warnings = Vec::new();
+        // strict: the first problem is fatal; lenient: every problem is recorded
+        let mut issue = |msg: String| -> Result<(), String> {
+            if strict {
+                return Err(msg);
+            }
+            warn!("{}", msg);
+            warnings.push(msg);
+            Ok(())
+        };
+
         let mut oem_name = [0u8; 8];
         oem_name.copy_from_slice(&bs[3..11]);
 
         // 0x55AA signature must be present
         let bs55aa = read_u16(510)?;
         if bs55aa != 0xAA55 {
-            return Err("Invalid boot signature (0x55AA missing)".into());
+            issue("Invalid boot signature (0x55AA missing)".into())?;
         }
 
-        let me = Self {
+        let mut jump_boot = [0u8; 3];
+        jump_boot.copy_from_slice(&bs[0..3]);
+        let must_be_zero = bs[0x0B..0x40].to_vec();
+
+        let mut me = Self {
+            jump_boot,
             oem_name,
+            must_be_zero_nonzero: must_be_zero.iter().filter(|&&b| b != 0).count(),
             partition_offset: read_u64(0x40)?,
             volume_length: read_u64(0x48)?,
             fat_offset: read_u32(0x50)?,
@@ -73,74 +302,117 @@ impl BootSector {
             sectors_per_cluster_shift: bs[0x6D],
             num_fats: bs[0x6E],
             drive_select: bs[0x6F],
+            percent_in_use: bs[0x70],
+            must_be_zero,
+            boot_code: bs[0x78..0x1FE].to_vec(),
         };
 
+        if me.jump_boot != [0xEB, 0x76, 0x90] {
+            warn!("Unexpected JumpBoot {:02X?}", me.jump_boot);
+        }
+        if me.must_be_zero_nonzero > 0 {
+            warn!(
+                "MustBeZero area holds {} non-zero bytes",
+                me.must_be_zero_nonzero
+            );
+        }
+
         if &me.oem_name != b"EXFAT   " {
-            return Err(format!(
+            issue(format!(
                 "OEM name isn't EXFAT: {:?}",
                 String::from_utf8_lossy(&me.oem_name)
-            ));
+            ))?;
         }
 
         if me.fs_revision != 0x0100 {
-            return Err(format!(
+            issue(format!(
                 "Unexpected exFAT fs_revision=0x{:04X}",
                 me.fs_revision
-            ));
+            ))?;
         }
 
-        if me.num_fats != 1 {
-            return Err(format!("num_fats={} (exFAT requires 1)", me.num_fats));
+        if !(1..=2).contains(&me.num_fats) {
+            issue(format!(
+                "num_fats={} (exFAT requires 1, TexFAT 2)",
+                me.num_fats
+            ))?;
+            me.num_fats = 1;
+        }
+        if me.num_fats == 1 && me.flags().active_fat != 0 {
+            warn!("ActiveFat=1 on a single-FAT volume; using the first FAT");
         }
 
         if me.volume_length == 0 {
-            return Err("volume_length is zero".into());
+            issue("volume_length is zero".into())?;
         }
         if me.fat_offset == 0 || me.fat_length == 0 {
-            return Err(format!(
+            issue(format!(
                 "Invalid FAT location/length: offset={}, length={}",
                 me.fat_offset, me.fat_length
-            ));
+            ))?;
         }
         if me.cluster_heap_offset == 0 || me.cluster_count < 2 {
-            return Err(format!(
+            issue(format!(
                 "Invalid cluster heap: offset={}, count={}",
                 me.cluster_heap_offset, me.cluster_count
-            ));
+            ))?;
         }
         if me.root_dir_first_cluster < 2 {
-            return Err(format!(
+            issue(format!(
                 "Invalid root_dir_first_cluster: {} (<2)",
                 me.root_dir_first_cluster
-            ));
+            ))?;
         }
 
         if !(9..=12).contains(&me.bytes_per_sector_shift) {
-            return Err(format!(
+            issue(format!(
                 "bytes_per_sector_shift={} not in [9..12]",
                 me.bytes_per_sector_shift
-            ));
+            ))?;
+            me.bytes_per_sector_shift = 9;
         }
-        if me.sectors_per_cluster_shift > 25 {
-            return Err(format!(
-                "sectors_per_cluster_shift={} too large",
+        // clusters range from one sector up to 32 MiB
+        if me.sectors_per_cluster_shift > 25 - me.bytes_per_sector_shift {
+            issue(format!(
+                "sectors_per_cluster_shift={} too large (bytes_per_cluster > 32MiB)",
                 me.sectors_per_cluster_shift
-            ));
+            ))?;
+            me.sectors_per_cluster_shift = 0;
         }
         let bps = me.bytes_per_sector();
         let spc = me.sectors_per_cluster();
         let bpc = me.bytes_per_cluster();
 
-        if !(4096..=32 * 1024 * 1024).contains(&bpc) {
-            return Err(format!("bytes_per_cluster={} outside [4KiB..32MiB]", bpc));
-        }
-
-        let fat_end_sector = me.fat_offset as u64 + me.fat_length as u64;
+        let fat_end_sector = me.fat_offset as u64 + me.fat_length as u64 * me.num_fats as u64;
         if fat_end_sector > me.cluster_heap_offset as u64 {
-            return Err(format!(
+            issue(format!(
                 "FAT overlaps cluster heap: FAT end={} >= heap offset={}",
                 fat_end_sector, me.cluster_heap_offset
-            ));
+            ))?;
+        }
+
+        // ClusterCount is bounded by both the FAT and the volume; a larger value would
+        // have readers walk (and allocate) past either
+        let fat_entries = me.fat_length as u64 * bps / 4;
+        if me.fat_length != 0 && me.cluster_count as u64 + 2 > fat_entries {
+            issue(format!(
+                "cluster_count={} does not fit the FAT ({} entries)",
+                me.cluster_count, fat_entries
+            ))?;
+            me.cluster_count = fat_entries.saturating_sub(2).min(u32::MAX as u64) as u32;
+            warn!("cluster_count clamped to {}", me.cluster_count);
+        }
+        let heap_clusters = me
+            .volume_length
+            .saturating_sub(me.cluster_heap_offset as u64)
+            / spc;
+        if me.volume_length != 0 && me.cluster_count as u64 > heap_clusters {
+            issue(format!(
+                "cluster_count={} exceeds the volume ({} clusters after the heap offset)",
+                me.cluster_count, heap_clusters
+            ))?;
+            me.cluster_count = heap_clusters.min(u32::MAX as u64) as u32;
+            warn!("cluster_count clamped to {}", me.cluster_count);
         }
 
         let first_data_cluster = 2u64;
@@ -148,15 +420,15 @@ impl BootSector {
         if (me.root_dir_first_cluster as u64) < first_data_cluster
             || (me.root_dir_first_cluster as u64) > last_cluster
         {
-            return Err(format!(
+            issue(format!(
                 "root_dir_first_cluster={} out of range [2..{}]",
                 me.root_dir_first_cluster, last_cluster
-            ));
+            ))?;
         }
 
         debug!("BPB OK: bps={} spc={} bpc={}", bps, spc, bpc);
 
-        Ok(me)
+        Ok((me, warnings))
     }
 
     #[inline]
@@ -172,9 +444,26 @@ impl BootSector {
         self.bytes_per_sector() * self.sectors_per_cluster()
     }
 
+    /// Index of the FAT and allocation bitmap in use (VolumeFlags.ActiveFat, TexFAT only).
+    #[inline]
+    pub fn active_fat(&self) -> u8 {
+        if self.num_fats > 1 {
+            self.flags().active_fat
+        } else {
+            0
+        }
+    }
+
+    /// Start of the active FAT.
     #[inline]
     pub fn fat_start_byte(&self) -> u64 {
-        self.fat_offset as u64 * self.bytes_per_sector()
+        self.fat_start_byte_of(self.active_fat())
+    }
+
+    /// Start of FAT number `index` (0 or 1); the second FAT directly follows the first.
+    #[inline]
+    pub fn fat_start_byte_of(&self, index: u8) -> u64 {
+        (self.fat_offset as u64 + index as u64 * self.fat_length as u64) * self.bytes_per_sector()
     }
 
     // Currently unused outside this module, keep for completeness.
@@ -192,11 +481,305 @@ impl BootSector {
         data_sector * self.bytes_per_sector()
     }
 
+    #[inline]
+    pub fn flags(&self) -> VolumeFlags {
+        VolumeFlags::from_raw(self.volume_flags)
+    }
+
+    /// Number of non-zero bytes in the 390-byte boot code area.
+    pub fn boot_code_nonzero(&self) -> usize {
+        self.boot_code.iter().filter(|&&b| b != 0).count()
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut v = serde_json::to_value(self).unwrap_or_else(|_| json!({}));
+        if let Some(o) = v.as_object_mut() {
+            o.insert("volume_flags_decoded".into(), json!(self.flags()));
+            o.insert("must_be_zero_hex".into(), json!(to_hex(&self.must_be_zero)));
+            o.insert("boot_code_nonzero".into(), json!(self.boot_code_nonzero()));
+            o.insert(
+                "volume_serial_formatted".into(),
+                json!(format_serial(self.volume_serial)),
+            );
+        }
+        v
+    }
+}
+
+/// Signature closing each extended boot sector (last 4 bytes of sectors 1..=8).
+pub const EXTENDED_BOOT_SIGNATURE: u32 = 0xAA55_0000;
+
+/// Flash Parameters GUID {0A0C7E46-3399-4021-90C8-FA6D389C4BA2}, in on-disk byte order.
+pub const FLASH_PARAMETERS_GUID: [u8; 16] = [
+    0x46, 0x7E, 0x0C, 0x0A, 0x99, 0x33, 0x21, 0x40, 0x90, 0xC8, 0xFA, 0x6D, 0x38, 0x9C, 0x4B, 0xA2,
+];
+
+/// Volume serial number in the `XXXX-XXXX` form of `vol` and the registry.
+pub fn format_serial(serial: u32) -> String {
+    format!("{:04X}-{:04X}", serial >> 16, serial & 0xFFFF)
+}
+
+/// Render a 16-byte on-disk GUID (first three groups little-endian) as text.
+pub fn format_guid(g: &[u8]) -> String {
+    if g.len() < 16 {
+        return String::new();
+    }
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        u32::from_le_bytes([g[0], g[1], g[2], g[3]]),
+        u16::from_le_bytes([g[4], g[5]]),
+        u16::from_le_bytes([g[6], g[7]]),
+        g[8],
+        g[9],
+        g[10],
+        g[11],
+        g[12],
+        g[13],
+        g[14],
+        g[15]
+    )
+}
+
+fn to_hex(b: &[u8]) -> String {
+    b.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+/// Extended boot sector (sectors 1..=8 of a boot region).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtendedBootSector {
+    pub sector: u8,
+    pub signature: u32,
+    pub valid_signature: bool,
+    pub code_is_zero: bool,
+}
+
+impl ExtendedBootSector {
+    pub fn from_bytes(sector: u8, b: &[u8]) -> Self {
+        let n = b.len();
+        let signature = if n >= 4 {
+            u32::from_le_bytes([b[n - 4], b[n - 3], b[n - 2], b[n - 1]])
+        } else {
+            0
+        };
+        Self {
+            sector,
+            signature,
+            valid_signature: signature == EXTENDED_BOOT_SIGNATURE,
+            code_is_zero: b[..n.saturating_sub(4)].iter().all(|&x| x == 0),
+        }
+    }
+
+    /// Parse sectors 1..=8 of a boot region.
+    pub fn all_from_region(region: &[u8], bytes_per_sector: usize) -> Vec<Self> {
+        (1..=8u8)
+            .filter_map(|i| {
+                let start = i as usize * bytes_per_sector;
+                region
+                    .get(start..start + bytes_per_sector)
+                    .map(|b| Self::from_bytes(i, b))
+            })
+            .collect()
+    }
+}
+
+/// Flash Parameters record of the OEM parameters sector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashParameters {
+    pub erase_block_size: u32,
+    pub page_size: u32,
+    pub spare_sections: u32,
+    pub random_access_time: u32, // ns
+    pub programming_time: u32,   // ns
+    pub read_cycle: u32,         // ns
+    pub write_cycle: u32,        // ns
+}
+
+/// One 48-byte parameter record of the OEM parameters sector.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OemParameterRecord {
+    pub slot: usize,
+    pub guid: String,
+    pub flash: Option<FlashParameters>,
+    pub raw_hex: Option<String>, // custom-defined bytes of unknown records
+}
+
+/// OEM parameters (sector 9 of a boot region): up to 10 GUID-tagged records.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OemParameters {
+    pub records: Vec<OemParameterRecord>,
+}
+
+impl OemParameters {
+    pub fn from_bytes(b: &[u8]) -> Self {
+        let mut records = Vec::new();
+        for (slot, rec) in b.chunks_exact(48).take(10).enumerate() {
+            let guid = &rec[0..16];
+            if guid.iter().all(|&x| x == 0) {
+                continue; // unused slot
+            }
+            let le_u32 =
+                |o: usize| u32::from_le_bytes([rec[o], rec[o + 1], rec[o + 2], rec[o + 3]]);
+            let flash = (guid == FLASH_PARAMETERS_GUID).then(|| FlashParameters {
+                erase_block_size: le_u32(16),
+                page_size: le_u32(20),
+                spare_sections: le_u32(24),
+                random_access_time: le_u32(28),
+                programming_time: le_u32(32),
+                read_cycle: le_u32(36),
+                write_cycle: le_u32(40),
+            });
+            let raw_hex = flash.is_none().then(|| to_hex(&rec[16..48]));
+            records.push(OemParameterRecord {
+                slot,
+                guid: format_guid(guid),
+                flash,
+                raw_hex,
+            });
+        }
+        Self { records }
+    }
+
+    pub fn flash(&self) -> Option<&FlashParameters> {
+        self.records.iter().find_map(|r| r.flash.as_ref())
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for OemParameters {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.records.is_empty() {
+            return writeln!(f, "OEM parameters: none");
+        }
+        let mut t = Table::new();
+        for r in &self.records {
+            t.add_row(Row::new(vec![
+                Cell::new(&format!("OEM record #{}", r.slot)),
+                Cell::new(&r.guid),
+            ]));
+            if let Some(fp) = &r.flash {
+                for (k, v) in [
+                    ("Erase block size", fp.erase_block_size),
+                    ("Page size", fp.page_size),
+                    ("Spare sections", fp.spare_sections),
+                    ("Random access time (ns)", fp.random_access_time),
+                    ("Programming time (ns)", fp.programming_time),
+                    ("Read cycle (ns)", fp.read_cycle),
+                    ("Write cycle (ns)", fp.write_cycle),
+                ] {
+                    t.add_row(Row::new(vec![Cell::new(k), Cell::new(&v.to_string())]));
+                }
+            } else if let Some(raw) = &r.raw_hex {
+                t.add_row(Row::new(vec![Cell::new("Raw"), Cell::new(raw)]));
+            }
+        }
+        write!(f, "{}", t)
+    }
+}
+
+/// One BPB field whose value differs between the main and backup boot sectors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootFieldDiff {
+    pub field: String,
+    pub main: Value,
+    pub backup: Value,
+}
+
+/// BPB fields the driver updates at runtime, excluded from the boot checksum; they
+/// normally differ between the main and backup boot sectors.
+const RUNTIME_FIELDS: &[&str] = &["volume_flags", "volume_flags_decoded", "percent_in_use"];
+
+/// Field-by-field comparison of the main and backup boot sectors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BootDiff {
+    pub fields: Vec<BootFieldDiff>,
+    /// Differences in runtime fields (VolumeFlags, PercentInUse): expected, not discrepancies.
+    pub runtime: Vec<BootFieldDiff>,
+}
+
+impl BootDiff {
+    pub fn between(main: &BootSector, backup: &BootSector) -> Self {
+        let (m, b) = (main.to_json(), backup.to_json());
+        let mut fields = Vec::new();
+        let mut runtime = Vec::new();
+        if let (Some(mo), Some(bo)) = (m.as_object(), b.as_object()) {
+            for (k, mv) in mo {
+                let bv = bo.get(k).cloned().unwrap_or(Value::Null);
+                if *mv != bv {
+                    let d = BootFieldDiff {
+                        field: k.clone(),
+                        main: mv.clone(),
+                        backup: bv,
+                    };
+                    if RUNTIME_FIELDS.contains(&k.as_str()) {
+                        runtime.push(d);
+                    } else {
+                        fields.push(d);
+                    }
+                }
+            }
+        }
+        // boot_code is not serialized: compare it byte for byte (tampering shows up here)
+        if main.boot_code != backup.boot_code {
+            let differing = main
+                .boot_code
+                .iter()
+                .zip(&backup.boot_code)
+                .filter(|(a, b)| a != b)
+                .count();
+            fields.push(BootFieldDiff {
+                field: format!("boot_code ({} bytes differ)", differing),
+                main: json!(format!("{:x}", Sha256::digest(&main.boot_code))),
+                backup: json!(format!("{:x}", Sha256::digest(&backup.boot_code))),
+            });
+        }
+        Self { fields, runtime }
+    }
+
+    /// No discrepancy outside the runtime fields.
+    #[inline]
+    pub fn is_identical(&self) -> bool {
+        self.fields.is_empty()
+    }
+
     pub fn to_json(&self) -> Value {
         serde_json::to_value(self).unwrap_or_else(|_| json!({}))
     }
 }
 
+impl std::fmt::Display for BootDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_identical() {
+            writeln!(f, "Main and backup boot sectors are identical.")?;
+        } else {
+            let mut t = Table::new();
+            t.add_row(Row::new(vec![
+                Cell::new("Field"),
+                Cell::new("Main"),
+                Cell::new("Backup"),
+            ]));
+            for d in &self.fields {
+                t.add_row(Row::new(vec![
+                    Cell::new(&d.field),
+                    Cell::new(&d.main.to_string()),
+                    Cell::new(&d.backup.to_string()),
+                ]));
+            }
+            write!(f, "{}", t)?;
+        }
+        for d in &self.runtime {
+            writeln!(
+                f,
+                "Expected runtime difference: {} (main {}, backup {})",
+                d.field, d.main, d.backup
+            )?;
+        }
+        Ok(())
+    }
+}
+
 impl std::fmt::Display for BootSector {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut t = Table::new();
@@ -220,6 +803,10 @@ impl std::fmt::Display for BootSector {
             Cell::new("FAT length (sectors)"),
             Cell::new(&self.fat_length.to_string()),
         ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Number of FATs"),
+            Cell::new(&self.num_fats.to_string()),
+        ]));
         t.add_row(Row::new(vec![
             Cell::new("Cluster heap offset (sectors)"),
             Cell::new(&self.cluster_heap_offset.to_string()),
@@ -228,9 +815,41 @@ impl std::fmt::Display for BootSector {
             Cell::new("Root dir first cluster"),
             Cell::new(&format!("{}", self.root_dir_first_cluster)),
         ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Volume serial"),
+            Cell::new(&format_serial(self.volume_serial)),
+        ]));
         t.add_row(Row::new(vec![
             Cell::new("Volume flags"),
-            Cell::new(&format!("0x{:04X}", self.volume_flags)),
+            Cell::new(&format!("0x{:04X} ({})", self.volume_flags, self.flags())),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Percent in use"),
+            Cell::new(&if self.percent_in_use == 0xFF {
+                "n/a (0xFF)".to_string()
+            } else {
+                format!("{}%", self.percent_in_use)
+            }),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Jump boot"),
+            Cell::new(&to_hex(&self.jump_boot)),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("MustBeZero"),
+            Cell::new(&if self.must_be_zero_nonzero == 0 {
+                "zero".to_string()
+            } else {
+                format!("{} non-zero bytes", self.must_be_zero_nonzero)
+            }),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Boot code"),
+            Cell::new(&format!(
+                "{} bytes, {} non-zero",
+                self.boot_code.len(),
+                self.boot_code_nonzero()
+            )),
         ]));
         write!(f, "{}", t)
     }
@@ -1,7 +1,6 @@
 /// Main source : https://arxiv.org/pdf/1804.08653
-use crate::direntry::{EntryType, FileDirectoryEntry, StreamExtensionEntry};
-use crate::fat::Fat;
 use crate::fs::{ExFatFS, FsError};
+use crate::walk::WalkOptions;
 use log::{debug, info, warn};
 use std::fs::{File, create_dir_all};
 use std::io::Write;
@@ -43,6 +42,7 @@ impl Magic {
 /// Inactive file metadata reconstructed from directory entries (0x80 cleared).
 #[derive(Clone, Debug)]
 pub struct InactiveMeta {
+    pub inode: u64, // fake inode of the entry set: (parent dir cluster << 32) | entry index
     pub name: String,
     pub first_cluster: u32,
     pub size: u64,
@@ -52,145 +52,44 @@ pub struct InactiveMeta {
     pub last_access_time: u32,
 }
 
-/// Find the Allocation Bitmap entry (0x81) in the root directory and read it into memory (bytes).
-fn read_allocation_bitmap<T: std::io::Read + std::io::Seek>(
-    fs: &mut ExFatFS<T>,
-) -> Result<Vec<u8>, FsError> {
-    // Read raw directory entries directly from the root
-    let raw = fs.read_dir_entries_from_chain(fs.bpb.root_dir_first_cluster)?;
-    let mut i = 0usize;
-
-    while i < raw.len() {
-        let e = &raw[i];
-        match EntryType::from(e.entry_type) {
-            EntryType::End => break,
-            EntryType::AllocationBitmap => {
-                // AllocationBitmap entry format:
-                // byte[1]  = bitmap flags (bit0 = active bitmap)
-                // byte[20] = first cluster (u32 LE)
-                // byte[24] = data length (u32 LE)
-                let b = &e.raw;
-                let first_cluster = u32::from_le_bytes([b[20], b[21], b[22], b[23]]);
-                let data_length = u32::from_le_bytes([b[24], b[25], b[26], b[27]]) as usize;
-
-                // Follow FAT chain if necessary
-                let mut fat = Fat::new(&fs.bpb, &mut fs.io);
-                let chain = fat.walk_chain(first_cluster, 1_000_000)?;
-
-                // Read all clusters into buffer
-                let mut buf = Vec::with_capacity(data_length);
-                for cl in chain {
-                    let block = fs.read_cluster(cl)?;
-                    buf.extend_from_slice(&block);
-                    if buf.len() >= data_length {
-                        break;
-                    }
-                }
-                buf.truncate(data_length);
-                return Ok(buf);
-            }
-            _ => {}
-        }
-        i += 1;
-    }
-
-    Err(FsError::NotFound(
-        "Allocation Bitmap not found in root".into(),
-    ))
-}
-
 /// Return **all inactive** file sets across the FS (https://arxiv.org/pdf/1804.08653)
 fn collect_inactive_entries<T: std::io::Read + std::io::Seek>(
     fs: &mut ExFatFS<T>,
 ) -> Result<Vec<InactiveMeta>, FsError> {
     let mut out = Vec::new();
-    let mut stack = vec![fs.bpb.root_dir_first_cluster];
-
-    while let Some(dir_clus) = stack.pop() {
-        let ents = fs.read_dir_entries_from_chain(dir_clus)?;
-        let mut i = 0usize;
-
-        // Walk entry sets: treat type with MSB forced to 1 while remembering original "active bit"
-        while i < ents.len() {
-            let e = &ents[i];
-            if e.entry_type == 0x00 {
-                break;
-            }
-
-            // Decode set whether active or inactive by normalizing type
-            let normalized_kind = EntryType::from(e.entry_type | 0x80);
-            if matches!(normalized_kind, EntryType::File) {
-                let sec_cnt = ents[i].raw[1] as usize;
-                let end = (i + 1 + sec_cnt).min(ents.len());
-                let active = (ents[i].entry_type & 0x80) != 0;
-
-                // Parse secondaries manually to extract flags & name even if inactive
-                let mut fde: Option<FileDirectoryEntry> = None;
-                let mut st: Option<StreamExtensionEntry> = None;
-                let mut name = String::new();
-
-                for e2 in &ents[i..end] {
-                    match EntryType::from(e2.entry_type | 0x80) {
-                        EntryType::File => {
-                            fde = Some(FileDirectoryEntry::parse(e2));
-                        }
-                        EntryType::StreamExt => {
-                            st = Some(StreamExtensionEntry::parse(e2));
-                        }
-                        EntryType::FileName => {
-                            name.push_str(&crate::direntry::FileNameEntry::parse(e2).name_fragment);
-                        }
-                        _ => {}
-                    }
-                }
-
-                if let (Some(fd), Some(st)) = (fde, st) {
-                    if !active {
-                        let uses_fat = (st.general_flags & 0x02) == 0; // bit set => no FAT (contiguous); so uses_fat = !bit
-                        out.push(InactiveMeta {
-                            name,
-                            first_cluster: st.first_cluster,
-                            size: st.data_length,
-                            uses_fat,
-                            create_time: fd.create_time,
-                            last_mod_time: fd.last_mod_time,
-                            last_access_time: fd.last_access_time,
-                        });
-                    } else if (fd.attributes & 0x0010) != 0 && st.first_cluster >= 2 {
-                        // active subdir: recurse
-                        stack.push(st.first_cluster);
-                    }
-                }
-                i = end;
-                continue;
-            }
-            i += 1;
+    let opts = WalkOptions {
+        include_deleted: true,
+        ..Default::default()
+    };
+    // the walk descends into active directories only and decodes inactive sets too
+    fs.walk(&opts, |e| {
+        if e.deleted {
+            let fr = e.record;
+            let uses_fat = (fr.general_flags & 0x02) == 0; // bit set => no FAT (contiguous); so uses_fat = !bit
+            out.push(InactiveMeta {
+                inode: e.inode,
+                name: fr.name,
+                first_cluster: fr.first_cluster,
+                size: fr.size,
+                uses_fat,
+                create_time: fr.create_time,
+                last_mod_time: fr.last_mod_time,
+                last_access_time: fr.last_access_time,
+            });
         }
-    }
+        Ok(())
+    })?;
 
     Ok(out)
 }
 
-fn bitmap_is_allocated(bitmap: &[u8], cluster: u32) -> bool {
-    if cluster < 2 {
-        return true; // treat as allocated / not for carving
-    }
-    let idx = (cluster - 2) as usize;
-    let byte = idx / 8;
-    let bit = idx % 8;
-    if byte >= bitmap.len() {
-        return true; // be safe
-    }
-    (bitmap[byte] & (1u8 << bit)) != 0
-}
-
 /// Carve unallocated clusters following the methodology (cluster-start signatures, metadata from inactive entries).
 pub fn carve<T: std::io::Read + std::io::Seek>(
     fs: &mut ExFatFS<T>,
     out_dir: &str,
     limit: Option<usize>,
 ) -> Result<usize, FsError> {
-    let bitmap = read_allocation_bitmap(fs)?;
+    let bitmap = fs.allocation_bitmap()?;
     let inact = collect_inactive_entries(fs)?;
     debug!("carve: inactive sets indexed = {}", inact.len());
 
@@ -201,7 +100,7 @@ pub fn carve<T: std::io::Read + std::io::Seek>(
 
     // Walk through all clusters; look only at unallocated ones (https://arxiv.org/pdf/1804.08653)
     while (cl as u64) < fs.bpb.cluster_count as u64 + 2 {
-        if !bitmap_is_allocated(&bitmap, cl) {
+        if !bitmap.is_allocated(cl) {
             // scan header at cluster start
             let buf = fs.read_cluster(cl)?;
             for m in Magic::all() {
@@ -232,7 +131,7 @@ pub fn carve<T: std::io::Read + std::io::Seek>(
 
                     // Read data
                     let data = if uses_fat {
-                        let mut fat = Fat::new(&fs.bpb, &mut fs.io);
+                        let mut fat = fs.fat();
                         let chain = fat.walk_chain(cl, 1_000_000)?;
                         let mut out = Vec::with_capacity(size as usize);
                         for c in chain {
@@ -250,7 +149,7 @@ pub fn carve<T: std::io::Read + std::io::Seek>(
                         let mut remaining = size as usize;
                         let mut cur = cl;
                         let mut out = Vec::with_capacity(remaining);
-                        while remaining > 0 && !bitmap_is_allocated(&bitmap, cur) {
+                        while remaining > 0 && !bitmap.is_allocated(cur) {
                             let blk = fs.read_cluster(cur)?;
                             let take = remaining.min(blk.len());
                             out.extend_from_slice(&blk[..take]);
@@ -273,7 +172,12 @@ pub fn carve<T: std::io::Read + std::io::Seek>(
                     };
                     let mut f = File::create(&fname)?;
                     f.write_all(&data)?;
-                    info!("carved {} bytes -> {}", data.len(), fname);
+                    match meta_opt.map(|meta| fs.path_of(meta.inode)) {
+                        Some(Ok(path)) => {
+                            info!("carved {} bytes -> {} (was {})", data.len(), fname, path)
+                        }
+                        _ => info!("carved {} bytes -> {}", data.len(), fname),
+                    }
 
                     found += 1;
                     if limit.is_some_and(|max| found >= max) {
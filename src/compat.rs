@@ -1,3 +1,4 @@
+use crate::direntry::FileRecord;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
@@ -8,18 +9,30 @@ pub struct CompatDirEntry {
     pub rec_len: u16,
     pub file_type: u8,
     pub name: String,
+    pub path: String, // full path, when known
 }
 
+/// ext directory entry file types (`EXT4_FT_*`).
+pub const EXT_FT_REG_FILE: u8 = 1;
+pub const EXT_FT_DIR: u8 = 2;
+
 impl CompatDirEntry {
     pub fn from_name_inode(name: &str, inode: u64, is_dir: bool) -> Self {
         // ext's file_type values don't map 1:1; we use a simple mapping:
         // 2 = directory, 1 = regular (only used for display)
-        let file_type = if is_dir { 2 } else { 1 };
+        let file_type = if is_dir { EXT_FT_DIR } else { EXT_FT_REG_FILE };
         Self {
             inode,
             rec_len: 32,
             file_type,
             name: name.to_string(),
+            path: String::new(),
+        }
+    }
+    pub fn from_record(fr: &FileRecord, inode: u64, path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            ..Self::from_name_inode(&fr.name, inode, fr.attrs().directory)
         }
     }
     pub fn to_json(&self) -> Value {
@@ -29,7 +42,12 @@ impl CompatDirEntry {
 
 impl std::fmt::Display for CompatDirEntry {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if !self.name.is_empty() {
+        if !self.path.is_empty() {
+            write!(
+                f, "0x{:016x} :  {} : 0x{:x}",
+                self.inode, self.path, self.file_type
+            )
+        } else if !self.name.is_empty() {
             write!(
                 f, "0x{:016x} :  {} : 0x{:x}",
                 self.inode, self.name, self.file_type
@@ -1,3 +1,4 @@
+use crate::bpb::format_guid;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 
@@ -7,8 +8,12 @@ pub enum EntryType {
     UpCaseTable,      // 0x82
     VolumeLabel,      // 0x83
     File,             // 0x85 (file directory entry – 1st in a set)
+    VolumeGuid,       // 0xA0
+    TexFatPadding,    // 0xA1 (reserved content, keeps sets off sector boundaries)
     StreamExt,        // 0xC0 (stream extension)
     FileName,         // 0xC1 (file name 15 UTF-16 chars)
+    VendorExtension,  // 0xE0
+    VendorAllocation, // 0xE1
     Unknown(u8),
     End, // 0x00 empty marks end of directory
 }
@@ -21,8 +26,12 @@ impl From<u8> for EntryType {
             0x82 => EntryType::UpCaseTable,
             0x83 => EntryType::VolumeLabel,
             0x85 => EntryType::File,
+            0xA0 => EntryType::VolumeGuid,
+            0xA1 => EntryType::TexFatPadding,
             0xC0 => EntryType::StreamExt,
             0xC1 => EntryType::FileName,
+            0xE0 => EntryType::VendorExtension,
+            0xE1 => EntryType::VendorAllocation,
             x => EntryType::Unknown(x),
         }
     }
@@ -32,15 +41,21 @@ impl From<u8> for EntryType {
 pub struct RawDirEnt {
     pub entry_type: u8,
     pub raw: [u8; 32],
+    pub volume_offset: u64, // where the entry was read, bytes from the start of the volume
 }
 
 impl RawDirEnt {
     pub fn from_bytes(b: &[u8]) -> Self {
+        Self::from_bytes_at(b, 0)
+    }
+
+    pub fn from_bytes_at(b: &[u8], volume_offset: u64) -> Self {
         let mut raw = [0u8; 32];
         raw.copy_from_slice(&b[0..32]);
         Self {
             entry_type: raw[0],
             raw,
+            volume_offset,
         }
     }
 
@@ -58,6 +73,57 @@ impl RawDirEnt {
     }
 }
 
+/// Decoded FileAttributes of a File entry (0x85).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct FileAttributes {
+    pub read_only: bool, // bit 0
+    pub hidden: bool,    // bit 1
+    pub system: bool,    // bit 2
+    pub directory: bool, // bit 4
+    pub archive: bool,   // bit 5
+    pub reserved: u16,   // bit 3 and bits 6..15, must be zero
+}
+
+impl FileAttributes {
+    pub const READ_ONLY: u16 = 0x0001;
+    pub const HIDDEN: u16 = 0x0002;
+    pub const SYSTEM: u16 = 0x0004;
+    pub const DIRECTORY: u16 = 0x0010;
+    pub const ARCHIVE: u16 = 0x0020;
+
+    pub fn from_raw(v: u16) -> Self {
+        Self {
+            read_only: v & Self::READ_ONLY != 0,
+            hidden: v & Self::HIDDEN != 0,
+            system: v & Self::SYSTEM != 0,
+            directory: v & Self::DIRECTORY != 0,
+            archive: v & Self::ARCHIVE != 0,
+            reserved: v & 0xFFC8,
+        }
+    }
+}
+
+impl std::fmt::Display for FileAttributes {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        for (set, name) in [
+            (self.read_only, "ReadOnly"),
+            (self.hidden, "Hidden"),
+            (self.system, "System"),
+            (self.directory, "Directory"),
+            (self.archive, "Archive"),
+        ] {
+            if set {
+                parts.push(name.to_string());
+            }
+        }
+        if self.reserved != 0 {
+            parts.push(format!("Reserved=0x{:04X}", self.reserved));
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileDirectoryEntry {
     // 0x85
@@ -67,6 +133,11 @@ pub struct FileDirectoryEntry {
     pub create_time: u32,
     pub last_mod_time: u32,
     pub last_access_time: u32,
+    pub create_10ms: u8, // 0..=199, added to the 2-second resolution
+    pub last_mod_10ms: u8,
+    pub create_utc_offset: u8, // bit 7: OffsetValid, bits 0..6: signed 15-minute steps
+    pub last_mod_utc_offset: u8,
+    pub last_access_utc_offset: u8,
 }
 
 impl FileDirectoryEntry {
@@ -81,6 +152,11 @@ impl FileDirectoryEntry {
             create_time: le_u32(8),
             last_mod_time: le_u32(12),
             last_access_time: le_u32(16),
+            create_10ms: b[20],
+            last_mod_10ms: b[21],
+            create_utc_offset: b[22],
+            last_mod_utc_offset: b[23],
+            last_access_utc_offset: b[24],
         }
     }
 }
@@ -89,6 +165,9 @@ impl FileDirectoryEntry {
 pub struct StreamExtensionEntry {
     // 0xC0
     pub general_flags: u8,
+    pub name_length: u8, // UTF-16 code units
+    pub name_hash: u16,
+    pub valid_data_length: u64,
     pub first_cluster: u32,
     pub data_length: u64,
 }
@@ -96,10 +175,14 @@ pub struct StreamExtensionEntry {
 impl StreamExtensionEntry {
     pub fn parse(raw: &RawDirEnt) -> Self {
         let b = &raw.raw;
+        let le_u16 = |o: usize| u16::from_le_bytes(b[o..o + 2].try_into().unwrap());
         let le_u32 = |o: usize| u32::from_le_bytes(b[o..o + 4].try_into().unwrap());
         let le_u64 = |o: usize| u64::from_le_bytes(b[o..o + 8].try_into().unwrap());
         Self {
             general_flags: b[1],
+            name_length: b[3],
+            name_hash: le_u16(4),
+            valid_data_length: le_u64(8),
             first_cluster: le_u32(20),
             data_length: le_u64(24),
         }
@@ -110,6 +193,8 @@ impl StreamExtensionEntry {
 pub struct FileNameEntry {
     // 0xC1
     pub name_fragment: String, // up to 15 UTF-16LE chars
+    #[serde(skip)]
+    pub units: [u16; 15], // raw code units, NULs included
 }
 
 impl FileNameEntry {
@@ -124,6 +209,7 @@ impl FileNameEntry {
         let name = String::from_utf16_lossy(&iter.collect::<Vec<_>>());
         Self {
             name_fragment: name,
+            units: u16s,
         }
     }
 }
@@ -131,6 +217,7 @@ impl FileNameEntry {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AllocationBitmapEntry {
     // 0x81
+    pub bitmap_flags: u8, // bit 0: 0 = first bitmap, 1 = second (TexFAT)
     pub first_cluster: u32,
     pub data_length: u32, // bytes
 }
@@ -140,6 +227,7 @@ impl AllocationBitmapEntry {
         let b = &raw.raw;
         let le_u32 = |o: usize| u32::from_le_bytes(b[o..o + 4].try_into().unwrap());
         Self {
+            bitmap_flags: b[1],
             first_cluster: le_u32(20),
             data_length: le_u32(24),
         }
@@ -149,6 +237,7 @@ impl AllocationBitmapEntry {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UpcaseTableEntry {
     // 0x82
+    pub table_checksum: u32,
     pub first_cluster: u32,
     pub data_length: u32,
 }
@@ -158,6 +247,7 @@ impl UpcaseTableEntry {
         let b = &raw.raw;
         let le_u32 = |o: usize| u32::from_le_bytes(b[o..o + 4].try_into().unwrap());
         Self {
+            table_checksum: le_u32(4),
             first_cluster: le_u32(20),
             data_length: le_u32(24),
         }
@@ -184,8 +274,77 @@ impl VolumeLabelEntry {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeGuidEntry {
+    // 0xA0
+    pub secondary_count: u8,
+    pub set_checksum: u16,
+    pub general_flags: u16,
+    pub guid: String,
+}
+
+impl VolumeGuidEntry {
+    pub fn parse(raw: &RawDirEnt) -> Self {
+        let b = &raw.raw;
+        let le_u16 = |o: usize| u16::from_le_bytes(b[o..o + 2].try_into().unwrap());
+        Self {
+            secondary_count: b[1],
+            set_checksum: le_u16(2),
+            general_flags: le_u16(4),
+            guid: format_guid(&b[6..22]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VendorExtensionEntry {
+    // 0xE0
+    pub general_flags: u8,
+    pub vendor_guid: String,
+    pub vendor_defined_hex: String, // 14 bytes
+}
+
+impl VendorExtensionEntry {
+    pub fn parse(raw: &RawDirEnt) -> Self {
+        let b = &raw.raw;
+        Self {
+            general_flags: b[1],
+            vendor_guid: format_guid(&b[2..18]),
+            vendor_defined_hex: b[18..32].iter().map(|x| format!("{:02x}", x)).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VendorAllocationEntry {
+    // 0xE1
+    pub general_flags: u8, // bit 1: NoFatChain
+    pub vendor_guid: String,
+    pub vendor_defined: u16,
+    pub first_cluster: u32,
+    pub data_length: u64,
+}
+
+impl VendorAllocationEntry {
+    pub fn parse(raw: &RawDirEnt) -> Self {
+        let b = &raw.raw;
+        let le_u32 = |o: usize| u32::from_le_bytes(b[o..o + 4].try_into().unwrap());
+        let le_u64 = |o: usize| u64::from_le_bytes(b[o..o + 8].try_into().unwrap());
+        Self {
+            general_flags: b[1],
+            vendor_guid: format_guid(&b[2..18]),
+            vendor_defined: u16::from_le_bytes([b[18], b[19]]),
+            first_cluster: le_u32(20),
+            data_length: le_u64(24),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileRecord {
     pub name: String,
+    /// The name as stored (UTF-16 code units, unpaired surrogates kept), for NameHash.
+    #[serde(skip)]
+    pub name_units: Vec<u16>,
     pub attributes: u16,
     pub first_cluster: u32,
     pub size: u64,
@@ -193,17 +352,50 @@ pub struct FileRecord {
     pub create_time: u32,
     pub last_mod_time: u32,
     pub last_access_time: u32,
+    pub create_10ms: u8,
+    pub last_mod_10ms: u8,
+    pub create_utc_offset: u8,
+    pub last_mod_utc_offset: u8,
+    pub last_access_utc_offset: u8,
+    pub name_length: u8,
+    pub name_hash: u16,
+    pub valid_data_length: u64,
+    pub set_checksum: u16,
+    pub set_checksum_valid: bool, // recomputed over the complete set
+    // vendor secondaries of this set
+    pub vendor_extensions: Vec<VendorExtensionEntry>,
+    pub vendor_allocations: Vec<VendorAllocationEntry>,
 }
 
 impl FileRecord {
+    pub fn attrs(&self) -> FileAttributes {
+        FileAttributes::from_raw(self.attributes)
+    }
     pub fn is_dir(&self) -> bool {
-        (self.attributes & 0x0010) != 0
+        self.attrs().directory
     }
     pub fn to_json(&self) -> Value {
         serde_json::to_value(self).unwrap_or_else(|_| json!({}))
     }
 }
 
+/// SetChecksum over a File entry set (primary + secondaries), skipping the checksum
+/// field itself. Entry types are taken with the InUse bit set, so deleted sets verify too.
+pub fn entry_set_checksum(set: &[RawDirEnt]) -> u16 {
+    let mut sum = 0u16;
+    for (n, e) in set.iter().enumerate() {
+        for (i, &b) in e.raw.iter().enumerate() {
+            let b = match i {
+                0 => b | 0x80,
+                2 | 3 if n == 0 => continue,
+                _ => b,
+            };
+            sum = sum.rotate_right(1).wrapping_add(b as u16);
+        }
+    }
+    sum
+}
+
 // Helper to assemble a set of 0x85 + 0xC0 + 0xC1... into a FileRecord
 pub fn assemble_file(set: &[RawDirEnt]) -> Option<FileRecord> {
     if set.is_empty() {
@@ -211,7 +403,9 @@ pub fn assemble_file(set: &[RawDirEnt]) -> Option<FileRecord> {
     }
     let mut fde: Option<FileDirectoryEntry> = None;
     let mut stream: Option<StreamExtensionEntry> = None;
-    let mut name = String::new();
+    let mut units: Vec<u16> = Vec::new();
+    let mut vendor_extensions = Vec::new();
+    let mut vendor_allocations = Vec::new();
 
     for e in set {
         match e.kind_normalized() {
@@ -222,14 +416,30 @@ pub fn assemble_file(set: &[RawDirEnt]) -> Option<FileRecord> {
                 stream = Some(StreamExtensionEntry::parse(e));
             }
             EntryType::FileName => {
-                name.push_str(&crate::direntry::FileNameEntry::parse(e).name_fragment);
+                units.extend_from_slice(&crate::direntry::FileNameEntry::parse(e).units);
+            }
+            EntryType::VendorExtension => {
+                vendor_extensions.push(VendorExtensionEntry::parse(e));
+            }
+            EntryType::VendorAllocation => {
+                vendor_allocations.push(VendorAllocationEntry::parse(e));
             }
             _ => {}
         }
     }
     if let (Some(fd), Some(st)) = (fde, stream) {
+        // NameLength code units; a zero or too large NameLength (damaged set) falls
+        // back to cutting at the first NUL
+        let complete = set.len() == 1 + fd.secondary_count as usize;
+        let len = st.name_length as usize;
+        let name_units: Vec<u16> = if len > 0 && len <= units.len() {
+            units[..len].to_vec()
+        } else {
+            units.into_iter().take_while(|&c| c != 0).collect()
+        };
         return Some(FileRecord {
-            name,
+            name: String::from_utf16_lossy(&name_units),
+            name_units,
             attributes: fd.attributes,
             first_cluster: st.first_cluster,
             size: st.data_length,
@@ -237,6 +447,18 @@ pub fn assemble_file(set: &[RawDirEnt]) -> Option<FileRecord> {
             create_time: fd.create_time,
             last_mod_time: fd.last_mod_time,
             last_access_time: fd.last_access_time,
+            create_10ms: fd.create_10ms,
+            last_mod_10ms: fd.last_mod_10ms,
+            create_utc_offset: fd.create_utc_offset,
+            last_mod_utc_offset: fd.last_mod_utc_offset,
+            last_access_utc_offset: fd.last_access_utc_offset,
+            name_length: st.name_length,
+            name_hash: st.name_hash,
+            valid_data_length: st.valid_data_length,
+            set_checksum: fd.set_checksum,
+            set_checksum_valid: complete && entry_set_checksum(set) == fd.set_checksum,
+            vendor_extensions,
+            vendor_allocations,
         });
     }
     None
@@ -0,0 +1,260 @@
+use crate::bpb::{BOOT_REGION_SECTORS, BootSector};
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{Read, Seek, SeekFrom};
+
+/// Where a volume was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DiscoverySource {
+    Mbr,
+    Ebr,
+    Gpt,
+    Scan,
+}
+
+/// An exFAT volume located inside a whole disk image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveredVolume {
+    pub offset: u64, // bytes from the start of the body
+    pub size: u64,   // bytes (volume_length * bytes_per_sector)
+    pub bytes_per_sector: u64,
+    pub source: DiscoverySource,
+    pub partition_index: Option<usize>,
+    pub bpb_partition_offset: u64, // PartitionOffset (0x40) as recorded in the VBR, sectors
+}
+
+impl DiscoveredVolume {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for DiscoveredVolume {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let idx = self
+            .partition_index
+            .map(|i| format!("#{}", i))
+            .unwrap_or_else(|| "-".into());
+        write!(
+            f,
+            "offset 0x{:x} ({})  size {} bytes ({} sectors)  {:?} {}",
+            self.offset,
+            self.offset,
+            self.size,
+            self.size / self.bytes_per_sector,
+            self.source,
+            idx
+        )
+    }
+}
+
+fn read_at<T: Read + Seek>(io: &mut T, off: u64, len: usize) -> std::io::Result<Vec<u8>> {
+    let mut b = vec![0u8; len];
+    io.seek(SeekFrom::Start(off))?;
+    io.read_exact(&mut b)?;
+    Ok(b)
+}
+
+/// Check for an exFAT VBR at `offset`.
+fn probe<T: Read + Seek>(io: &mut T, offset: u64) -> Option<BootSector> {
+    let b = read_at(io, offset, 512).ok()?;
+    if &b[3..11] != b"EXFAT   " {
+        return None;
+    }
+    match BootSector::from_bytes(&b) {
+        Ok(bpb) => Some(bpb),
+        Err(e) => {
+            warn!("exFAT signature at 0x{:x} but invalid VBR: {}", offset, e);
+            None
+        }
+    }
+}
+
+fn volume(
+    offset: u64,
+    bpb: &BootSector,
+    source: DiscoverySource,
+    partition_index: Option<usize>,
+) -> DiscoveredVolume {
+    DiscoveredVolume {
+        offset,
+        size: bpb.volume_length * bpb.bytes_per_sector(),
+        bytes_per_sector: bpb.bytes_per_sector(),
+        source,
+        partition_index,
+        bpb_partition_offset: bpb.partition_offset,
+    }
+}
+
+/// Largest GPT partition entry array read (the usual one is 128 * 128 bytes = 16 KiB).
+const GPT_MAX_TABLE_BYTES: usize = 1024 * 1024;
+
+/// Partition start LBAs from a GPT, if the header at LBA 1 is valid.
+fn gpt_partitions<T: Read + Seek>(io: &mut T, ss: u64) -> Option<Vec<u64>> {
+    let hdr = read_at(io, ss, 92).ok()?;
+    if &hdr[0..8] != b"EFI PART" {
+        return None;
+    }
+    let le_u32 = |o: usize| u32::from_le_bytes(hdr[o..o + 4].try_into().unwrap());
+    let entries_lba = u64::from_le_bytes(hdr[0x48..0x50].try_into().unwrap());
+    let count = le_u32(0x50).min(1024) as usize;
+    let entry_size = le_u32(0x54) as usize;
+    // UEFI: 128 * 2^n bytes; the header is untrusted, so bound every product
+    if !entry_size.is_power_of_two() || !(128..=4096).contains(&entry_size) {
+        warn!("GPT: invalid partition entry size {}", entry_size);
+        return None;
+    }
+    let Some(table_len) = count
+        .checked_mul(entry_size)
+        .filter(|&n| n <= GPT_MAX_TABLE_BYTES)
+    else {
+        warn!(
+            "GPT: partition table of {} x {} bytes too large",
+            count, entry_size
+        );
+        return None;
+    };
+    let Some(table_off) = entries_lba.checked_mul(ss) else {
+        warn!("GPT: partition entries LBA {} out of range", entries_lba);
+        return None;
+    };
+    let table = read_at(io, table_off, table_len).ok()?;
+    let mut out = Vec::new();
+    for e in table.chunks_exact(entry_size) {
+        if e[0..16].iter().all(|&b| b == 0) {
+            continue; // unused entry
+        }
+        out.push(u64::from_le_bytes(e[32..40].try_into().unwrap()));
+    }
+    debug!("GPT: {} partition entries", out.len());
+    Some(out)
+}
+
+/// Partition start LBAs from an MBR, following extended partitions.
+/// Returns `None` if sector 0 carries no MBR or only a GPT protective entry.
+fn mbr_partitions<T: Read + Seek>(io: &mut T, ss: u64) -> Option<Vec<(u64, DiscoverySource)>> {
+    let mbr = read_at(io, 0, 512).ok()?;
+    if mbr[510] != 0x55 || mbr[511] != 0xAA || &mbr[3..11] == b"EXFAT   " {
+        return None;
+    }
+    let entry = |b: &[u8], i: usize| {
+        let e = &b[446 + i * 16..446 + (i + 1) * 16];
+        let lba = u32::from_le_bytes(e[8..12].try_into().unwrap()) as u64;
+        let len = u32::from_le_bytes(e[12..16].try_into().unwrap()) as u64;
+        (e[4], lba, len)
+    };
+
+    let mut out = Vec::new();
+    for i in 0..4 {
+        let (ptype, lba, len) = entry(&mbr, i);
+        match ptype {
+            0x00 => {}
+            0xEE => return None, // protective MBR, the GPT holds the partitions
+            0x05 | 0x0F | 0x85 => {
+                // walk the EBR chain; logical partitions are relative to their EBR,
+                // the next EBR is relative to the start of the extended partition
+                let mut ebr = lba;
+                for _ in 0..128 {
+                    let Ok(b) = read_at(io, ebr * ss, 512) else {
+                        break;
+                    };
+                    if b[510] != 0x55 || b[511] != 0xAA {
+                        break;
+                    }
+                    let (lt, llba, _) = entry(&b, 0);
+                    if lt != 0 && llba != 0 {
+                        out.push((ebr + llba, DiscoverySource::Ebr));
+                    }
+                    let (nt, nlba, _) = entry(&b, 1);
+                    if nt == 0 || nlba == 0 {
+                        break;
+                    }
+                    ebr = lba + nlba;
+                }
+            }
+            _ if lba != 0 && len != 0 => out.push((lba, DiscoverySource::Mbr)),
+            _ => {}
+        }
+    }
+    Some(out)
+}
+
+/// Scan sector-aligned offsets for exFAT VBRs, skipping over each volume found.
+fn scan<T: Read + Seek>(io: &mut T, ss: u64, image_size: u64) -> Vec<DiscoveredVolume> {
+    const CHUNK: u64 = 1024 * 1024;
+    let mut out = Vec::new();
+    let mut rejected = Vec::new();
+    let mut base = 0u64;
+    while base < image_size {
+        let len = CHUNK.min(image_size - base) as usize;
+        let buf = match read_at(io, base, len) {
+            Ok(b) => b,
+            Err(e) => {
+                warn!("scan: read failed at 0x{:x}: {}", base, e);
+                base += CHUNK;
+                continue;
+            }
+        };
+        let mut next = base + len as u64;
+        for (i, sec) in buf.chunks(ss as usize).enumerate() {
+            if sec.len() < 11 || &sec[3..11] != b"EXFAT   " {
+                continue;
+            }
+            let off = base + i as u64 * ss;
+            // the backup VBR, 12 sectors after a volume already found (or after a
+            // rejected main VBR), is not another volume
+            if out.iter().any(|v: &DiscoveredVolume| {
+                off == v.offset + BOOT_REGION_SECTORS * v.bytes_per_sector
+            }) || rejected.contains(&off.wrapping_sub(BOOT_REGION_SECTORS * ss))
+            {
+                continue;
+            }
+            let Some(bpb) = probe(io, off) else {
+                rejected.push(off);
+                continue;
+            };
+            let v = volume(off, &bpb, DiscoverySource::Scan, None);
+            info!("scan: exFAT VBR at 0x{:x}", off);
+            next = off.saturating_add(v.size.max(ss));
+            out.push(v);
+            break;
+        }
+        base = next;
+    }
+    out
+}
+
+/// Locate exFAT volumes in a whole disk image: GPT first, then MBR (with extended
+/// partitions); if neither yields a volume, scan sector-aligned offsets for the VBR signature.
+/// `sector_size` is the body sector size, used for partition table LBAs.
+pub fn discover_volumes<T: Read + Seek>(
+    io: &mut T,
+    sector_size: u64,
+    image_size: u64,
+) -> Vec<DiscoveredVolume> {
+    let ss = sector_size.max(512);
+    let mut out = Vec::new();
+
+    let starts: Vec<(u64, DiscoverySource)> = match gpt_partitions(io, ss) {
+        Some(p) => p.into_iter().map(|l| (l, DiscoverySource::Gpt)).collect(),
+        None => mbr_partitions(io, ss).unwrap_or_default(),
+    };
+    for (idx, (lba, src)) in starts.into_iter().enumerate() {
+        let Some(off) = lba.checked_mul(ss) else {
+            warn!("partition #{} starts at LBA {}, out of range", idx, lba);
+            continue;
+        };
+        if let Some(bpb) = probe(io, off) {
+            out.push(volume(off, &bpb, src, Some(idx)));
+        } else {
+            debug!("partition #{} at 0x{:x} is not exFAT", idx, off);
+        }
+    }
+
+    if out.is_empty() {
+        info!("no exFAT volume in partition tables; scanning for VBR signatures");
+        out = scan(io, ss, image_size);
+    }
+    out
+}
@@ -1,5 +1,5 @@
-use crate::direntry::FileRecord;
-use chrono::{DateTime, NaiveDate, SecondsFormat};
+use crate::direntry::{FileAttributes, FileRecord, VendorAllocationEntry, VendorExtensionEntry};
+use chrono::{DateTime, FixedOffset, NaiveDate, SecondsFormat};
 use prettytable::{Cell, Row, Table};
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
@@ -9,19 +9,54 @@ use serde_json::{Value, json};
 pub struct ExInode {
     pub i_num: u64,
     pub attributes: u16,
+    // synthesized ext-style mode and link count (exFAT has neither)
+    pub i_mode: u16,
+    pub i_links_count: u16,
     pub first_cluster: u32,
     pub size: u64,
     pub name: String,
+    pub path: String, // full path, filled in by ExFatFS
+    // UNIX seconds; UTC when the matching *_utc_offset is known, otherwise the
+    // local wall-clock time read as UTC
     pub create_time: i64,
     pub last_mod_time: i64,
     pub last_access_time: i64,
+    pub create_time_nsec: u32, // from the 10 ms increment
+    pub last_mod_time_nsec: u32,
+    // minutes east of UTC (None: OffsetValid not set, timezone unknown)
+    pub create_utc_offset: Option<i16>,
+    pub last_mod_utc_offset: Option<i16>,
+    pub last_access_utc_offset: Option<i16>,
+    pub valid_data_length: u64,
+    pub name_length: u8,
+    pub name_hash: u16,
+    // NameHash checked against the up-case table (None if not checked)
+    pub name_hash_valid: Option<bool>,
+    pub set_checksum: u16,
+    pub set_checksum_valid: bool,
+    pub vendor_extensions: Vec<VendorExtensionEntry>,
+    pub vendor_allocations: Vec<VendorAllocationEntry>,
+    // made up by ExFatFS (recovered lost chain): no entry set, checksum or timestamps
+    #[serde(default)]
+    pub synthetic: bool,
 }
 
-/// Convert a 32-bit FAT/exFAT timestamp (date<<16 | time) to UNIX epoch seconds (UTC).
-/// Assumptions:
-/// - Timestamp is interpreted as a naive UTC time (exFAT stores local time; TZ info is not in this 32-bit value).
-/// - 2-second granularity for seconds field; clamp 60 → 59.
-fn exfat_ts_to_unix(ts: u32) -> i64 {
+/// Decode a UtcOffset field: minutes east of UTC if OffsetValid (bit 7) is set.
+fn utc_offset_minutes(raw: u8) -> Option<i16> {
+    if raw & 0x80 == 0 {
+        return None;
+    }
+    // bits 0..6: signed 7-bit count of 15-minute steps
+    let steps = ((raw << 1) as i8 >> 1) as i16;
+    Some(steps * 15)
+}
+
+/// Convert a 32-bit FAT/exFAT timestamp (date<<16 | time) to UNIX epoch seconds and nanoseconds.
+/// - `inc_10ms` (0..=199) refines the 2-second resolution of the seconds field.
+/// - With a valid UTC offset the local time is shifted to UTC; without one the local
+///   wall-clock time is returned as if it were UTC.
+/// - Invalid dates give the sentinel -1.
+fn exfat_ts_to_unix(ts: u32, inc_10ms: u8, utc_offset: Option<i16>) -> (i64, u32) {
     let time = (ts & 0xFFFF) as u16;
     let date = (ts >> 16) as u16;
 
@@ -34,44 +69,122 @@ fn exfat_ts_to_unix(ts: u32) -> i64 {
 
     // Basic sanity checks, mirror your original guard.
     if month == 0 || month > 12 || day == 0 || day > 31 || hours > 23 || mins > 59 {
-        return -1; // sentinel for "invalid"
+        return (-1, 0); // sentinel for "invalid"
     }
 
     // Build a NaiveDateTime and convert to epoch seconds.
     let date = match NaiveDate::from_ymd_opt(year as i32, month, day) {
         Some(d) => d,
-        None => return -1,
+        None => return (-1, 0),
     };
     let ndt = date
         .and_hms_opt(hours, mins, secs.min(59))
         .unwrap_or_else(|| date.and_hms_opt(hours, mins, 59).unwrap());
 
-    ndt.and_utc().timestamp()
+    let inc = inc_10ms.min(199) as i64;
+    let local = ndt.and_utc().timestamp() + inc / 100;
+    let nanos = (inc % 100) as u32 * 10_000_000;
+    (local - utc_offset.unwrap_or(0) as i64 * 60, nanos)
 }
 
-/// Render UNIX seconds (UTC) as ISO-8601. If invalid (<0), show the raw sentinel.
-fn unix_to_iso(secs: i64) -> String {
+/// Render UNIX seconds as ISO-8601 with millisecond precision: in the recorded
+/// timezone when known, else as a local time without offset. Invalid (<0) shows the sentinel.
+fn unix_to_iso(secs: i64, nanos: u32, utc_offset: Option<i16>) -> String {
     if secs < 0 {
         return format!("{}", secs);
     }
-    match DateTime::from_timestamp(secs, 0) {
-        Some(ndt) => ndt.to_rfc3339_opts(SecondsFormat::Secs, true),
-        None => format!("{}", secs),
+    let Some(dt) = DateTime::from_timestamp(secs, nanos) else {
+        return format!("{}", secs);
+    };
+    match utc_offset.and_then(|m| FixedOffset::east_opt(m as i32 * 60)) {
+        Some(tz) => dt
+            .with_timezone(&tz)
+            .to_rfc3339_opts(SecondsFormat::Millis, false),
+        None => dt.naive_utc().format("%Y-%m-%dT%H:%M:%S%.3f").to_string(),
+    }
+}
+
+pub const S_IFDIR: u16 = 0o040000;
+pub const S_IFREG: u16 = 0o100000;
+
+/// POSIX mode as the Linux exfat driver presents it with the default umask (022):
+/// 0777 masked to 0755 for files and directories alike, write bits dropped for ReadOnly.
+pub fn synthesize_mode(attrs: FileAttributes) -> u16 {
+    let kind = if attrs.directory { S_IFDIR } else { S_IFREG };
+    let perm = if attrs.read_only { 0o555 } else { 0o755 };
+    kind | perm
+}
+
+/// `ls -l` style rendering of a mode, e.g. `drwxr-xr-x`.
+pub fn mode_string(mode: u16) -> String {
+    let mut s = String::with_capacity(10);
+    s.push(if mode & S_IFDIR != 0 { 'd' } else { '-' });
+    for shift in [6, 3, 0] {
+        let p = (mode >> shift) & 0o7;
+        s.push(if p & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if p & 0o2 != 0 { 'w' } else { '-' });
+        s.push(if p & 0o1 != 0 { 'x' } else { '-' });
+    }
+    s
+}
+
+/// Render a raw timestamp with its 10 ms increment and UtcOffset byte, as in `ExInode`.
+pub fn format_timestamp(ts: u32, inc_10ms: u8, utc_offset: u8) -> String {
+    let off = utc_offset_minutes(utc_offset);
+    let (secs, nanos) = exfat_ts_to_unix(ts, inc_10ms, off);
+    unix_to_iso(secs, nanos, off)
+}
+
+/// Render a UtcOffset byte, e.g. `+02:00`, or `not set` without OffsetValid.
+pub fn format_utc_offset(raw: u8) -> String {
+    match utc_offset_minutes(raw) {
+        Some(m) => format!(
+            "{}{:02}:{:02}",
+            if m < 0 { '-' } else { '+' },
+            m.abs() / 60,
+            m.abs() % 60
+        ),
+        None => "not set".into(),
     }
 }
 
 impl ExInode {
     pub fn from_record(i_num: u64, fr: &FileRecord) -> Self {
+        let create_off = utc_offset_minutes(fr.create_utc_offset);
+        let mod_off = utc_offset_minutes(fr.last_mod_utc_offset);
+        let access_off = utc_offset_minutes(fr.last_access_utc_offset);
+        let (create_time, create_time_nsec) =
+            exfat_ts_to_unix(fr.create_time, fr.create_10ms, create_off);
+        let (last_mod_time, last_mod_time_nsec) =
+            exfat_ts_to_unix(fr.last_mod_time, fr.last_mod_10ms, mod_off);
         Self {
             i_num,
             attributes: fr.attributes,
+            i_mode: synthesize_mode(fr.attrs()),
+            // directories: "." and the parent entry; subdirectories are added by ExFatFS
+            i_links_count: if fr.is_dir() { 2 } else { 1 },
             first_cluster: fr.first_cluster,
             size: fr.size,
             name: fr.name.clone(),
+            path: String::new(),
             // Convert exFAT raw timestamps to UNIX seconds here:
-            create_time: exfat_ts_to_unix(fr.create_time),
-            last_mod_time: exfat_ts_to_unix(fr.last_mod_time),
-            last_access_time: exfat_ts_to_unix(fr.last_access_time),
+            create_time,
+            last_mod_time,
+            last_access_time: exfat_ts_to_unix(fr.last_access_time, 0, access_off).0,
+            create_time_nsec,
+            last_mod_time_nsec,
+            create_utc_offset: create_off,
+            last_mod_utc_offset: mod_off,
+            last_access_utc_offset: access_off,
+            valid_data_length: fr.valid_data_length,
+            name_length: fr.name_length,
+            name_hash: fr.name_hash,
+            name_hash_valid: None,
+            set_checksum: fr.set_checksum,
+            set_checksum_valid: fr.set_checksum_valid,
+            vendor_extensions: fr.vendor_extensions.clone(),
+            vendor_allocations: fr.vendor_allocations.clone(),
+            synthetic: false,
         }
     }
 
@@ -80,16 +193,56 @@ impl ExInode {
         self.size
     }
     #[inline]
+    pub fn attrs(&self) -> FileAttributes {
+        FileAttributes::from_raw(self.attributes)
+    }
+    #[inline]
     pub fn is_dir(&self) -> bool {
-        (self.attributes & 0x0010) != 0
+        self.attrs().directory
     }
     #[inline]
     pub fn is_regular_file(&self) -> bool {
         !self.is_dir()
     }
 
+    pub fn create_time_iso(&self) -> String {
+        unix_to_iso(
+            self.create_time,
+            self.create_time_nsec,
+            self.create_utc_offset,
+        )
+    }
+    pub fn last_mod_time_iso(&self) -> String {
+        unix_to_iso(
+            self.last_mod_time,
+            self.last_mod_time_nsec,
+            self.last_mod_utc_offset,
+        )
+    }
+    pub fn last_access_time_iso(&self) -> String {
+        unix_to_iso(self.last_access_time, 0, self.last_access_utc_offset)
+    }
+
     pub fn to_json(&self) -> Value {
-        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+        let mut v = serde_json::to_value(self).unwrap_or_else(|_| json!({}));
+        v["create_time_iso"] = json!(self.create_time_iso());
+        v["last_mod_time_iso"] = json!(self.last_mod_time_iso());
+        v["last_access_time_iso"] = json!(self.last_access_time_iso());
+        if self.synthetic {
+            for k in [
+                "set_checksum",
+                "set_checksum_valid",
+                "create_time",
+                "last_mod_time",
+                "last_access_time",
+                "create_time_iso",
+                "last_mod_time_iso",
+                "last_access_time_iso",
+            ] {
+                v[k] = Value::Null;
+            }
+        }
+        v
     }
 }
 
@@ -102,7 +255,19 @@ impl std::fmt::Display for ExInode {
         ]));
         t.add_row(Row::new(vec![
             Cell::new("Attributes"),
-            Cell::new(&format!("0x{:04x}", self.attributes)),
+            Cell::new(&format!("0x{:04x} ({})", self.attributes, self.attrs())),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Mode"),
+            Cell::new(&format!(
+                "0{:o} ({})",
+                self.i_mode,
+                mode_string(self.i_mode)
+            )),
+        ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Links"),
+            Cell::new(&self.i_links_count.to_string()),
         ]));
         t.add_row(Row::new(vec![
             Cell::new("First cluster"),
@@ -116,18 +281,86 @@ impl std::fmt::Display for ExInode {
             Cell::new("Dir?"),
             Cell::new(&format!("{}", self.is_dir())),
         ]));
+        t.add_row(Row::new(vec![
+            Cell::new("Valid data length"),
+            Cell::new(&format!("{}", self.valid_data_length)),
+        ]));
         t.add_row(Row::new(vec![Cell::new("Name"), Cell::new(&self.name)]));
+        if !self.path.is_empty() {
+            t.add_row(Row::new(vec![Cell::new("Path"), Cell::new(&self.path)]));
+        }
+        let hash_state = match self.name_hash_valid {
+            Some(true) => "valid",
+            Some(false) => "MISMATCH",
+            None => "not checked",
+        };
+        t.add_row(Row::new(vec![
+            Cell::new("Name hash"),
+            Cell::new(&format!("0x{:04x} ({})", self.name_hash, hash_state)),
+        ]));
+        let checksum = if self.synthetic {
+            "n/a (no entry set on disk)".to_string()
+        } else {
+            format!(
+                "0x{:04x} ({})",
+                self.set_checksum,
+                if self.set_checksum_valid {
+                    "valid"
+                } else {
+                    "MISMATCH"
+                }
+            )
+        };
+        t.add_row(Row::new(vec![
+            Cell::new("Set checksum"),
+            Cell::new(&checksum),
+        ]));
+        for v in &self.vendor_extensions {
+            t.add_row(Row::new(vec![
+                Cell::new("Vendor extension"),
+                Cell::new(&format!("{} {}", v.vendor_guid, v.vendor_defined_hex)),
+            ]));
+        }
+        for v in &self.vendor_allocations {
+            t.add_row(Row::new(vec![
+                Cell::new("Vendor allocation"),
+                Cell::new(&format!(
+                    "{} cluster {} ({} bytes)",
+                    v.vendor_guid, v.first_cluster, v.data_length
+                )),
+            ]));
+        }
+        if self.synthetic {
+            for k in ["Created", "Modified", "Accessed"] {
+                t.add_row(Row::new(vec![Cell::new(k), Cell::new("n/a")]));
+            }
+            return write!(f, "{}", t);
+        }
+        // a timestamp without offset is local time in an unknown timezone
+        let zone = |o: Option<i16>| if o.is_some() { "" } else { " (local)" };
         t.add_row(Row::new(vec![
             Cell::new("Created"),
-            Cell::new(&unix_to_iso(self.create_time)),
+            Cell::new(&format!(
+                "{}{}",
+                self.create_time_iso(),
+                zone(self.create_utc_offset)
+            )),
         ]));
         t.add_row(Row::new(vec![
             Cell::new("Modified"),
-            Cell::new(&unix_to_iso(self.last_mod_time)),
+            Cell::new(&format!(
+                "{}{}",
+                self.last_mod_time_iso(),
+                zone(self.last_mod_utc_offset)
+            )),
         ]));
         t.add_row(Row::new(vec![
             Cell::new("Accessed"),
-            Cell::new(&unix_to_iso(self.last_access_time)),
+            Cell::new(&format!(
+                "{}{}",
+                self.last_access_time_iso(),
+                zone(self.last_access_utc_offset)
+            )),
         ]));
         write!(f, "{}", t)
     }
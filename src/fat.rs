@@ -1,8 +1,17 @@
 use crate::bpb::BootSector;
 use log::{debug, warn};
-use std::collections::HashSet;
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::{Read, Seek, SeekFrom};
 
+/// Default memory budget of the FAT cache (64 MiB covers the FAT of a ~16M-cluster volume).
+pub const DEFAULT_FAT_CACHE_BYTES: usize = 64 * 1024 * 1024;
+
+/// FAT cache page size; pages are aligned on this boundary.
+const FAT_PAGE_BYTES: u64 = 64 * 1024;
+
 /// exFAT uses 32-bit FAT entries. High 4 bits are reserved in FAT32; in exFAT full 32 are used.
 /// End-of-chain markers are >= 0xFFFFFFF8.
 #[inline]
@@ -10,18 +19,130 @@ pub fn is_eoc(v: u32) -> bool {
     v >= 0xFFFFFFF8
 }
 
+/// Paged, size-bounded cache of FAT bytes shared by every chain walk of a volume.
+/// Pages are loaded on demand and the least recently used one is evicted first.
+pub struct FatCache {
+    max_pages: usize,
+    pages: HashMap<u64, (Vec<u8>, u64)>, // page byte offset -> (data, last use)
+    lru: BTreeMap<u64, u64>,             // last use -> page byte offset, oldest first
+    tick: u64,
+    hits: u64,
+    misses: u64,
+}
+
+impl FatCache {
+    /// `max_bytes == 0` disables caching.
+    pub fn new(max_bytes: usize) -> Self {
+        Self {
+            max_pages: max_bytes / FAT_PAGE_BYTES as usize,
+            pages: HashMap::new(),
+            lru: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        self.max_pages > 0
+    }
+    pub fn hits(&self) -> u64 {
+        self.hits
+    }
+    pub fn misses(&self) -> u64 {
+        self.misses
+    }
+    pub fn cached_bytes(&self) -> usize {
+        self.pages.values().map(|(p, _)| p.len()).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.lru.clear();
+    }
+
+    /// Read the u32 at absolute byte offset `off` (4-byte aligned), loading its page if needed.
+    fn read_u32<T: Read + Seek>(&mut self, io: &mut T, off: u64) -> std::io::Result<u32> {
+        let page = off - off % FAT_PAGE_BYTES;
+        let rel = (off - page) as usize;
+        self.tick += 1;
+        if let Some((data, used)) = self.pages.get_mut(&page) {
+            self.hits += 1;
+            self.lru.remove(used);
+            *used = self.tick;
+            self.lru.insert(self.tick, page);
+            return le_u32_at(data, rel);
+        }
+        self.misses += 1;
+
+        if self.pages.len() >= self.max_pages
+            && let Some((_, oldest)) = self.lru.pop_first()
+        {
+            self.pages.remove(&oldest);
+        }
+
+        // the last page may run past the end of the image
+        let mut data = vec![0u8; FAT_PAGE_BYTES as usize];
+        io.seek(SeekFrom::Start(page))?;
+        let mut got = 0;
+        while got < data.len() {
+            match io.read(&mut data[got..])? {
+                0 => break,
+                k => got += k,
+            }
+        }
+        data.truncate(got);
+        let v = le_u32_at(&data, rel);
+        self.pages.insert(page, (data, self.tick));
+        self.lru.insert(self.tick, page);
+        v
+    }
+}
+
+fn le_u32_at(data: &[u8], rel: usize) -> std::io::Result<u32> {
+    data.get(rel..rel + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof))
+}
+
 pub struct Fat<'a, T: Read + Seek> {
     pub bs: &'a BootSector,
     pub io: &'a mut T,
+    fat_start: u64,
+    cache: Option<&'a mut FatCache>,
 }
 
 impl<'a, T: Read + Seek> Fat<'a, T> {
+    /// Reader over the active FAT.
     pub fn new(bs: &'a BootSector, io: &'a mut T) -> Self {
-        Self { bs, io }
+        Self::with_index(bs, io, bs.active_fat())
+    }
+
+    /// Reader over FAT number `index` (TexFAT volumes have two).
+    pub fn with_index(bs: &'a BootSector, io: &'a mut T, index: u8) -> Self {
+        Self {
+            bs,
+            io,
+            fat_start: bs.fat_start_byte_of(index),
+            cache: None,
+        }
+    }
+
+    /// Reader over the active FAT going through `cache`.
+    pub fn with_cache(bs: &'a BootSector, io: &'a mut T, cache: &'a mut FatCache) -> Self {
+        let mut me = Self::new(bs, io);
+        if cache.enabled() {
+            me.cache = Some(cache);
+        }
+        me
     }
 
     pub fn read_entry(&mut self, cluster: u32) -> std::io::Result<u32> {
-        let fat_byte = self.bs.fat_start_byte() + (cluster as u64 * 4);
+        let fat_byte = self.fat_start + (cluster as u64 * 4);
+        if let Some(cache) = self.cache.as_deref_mut() {
+            return cache.read_u32(self.io, fat_byte);
+        }
         self.io.seek(SeekFrom::Start(fat_byte))?;
         let mut b = [0u8; 4];
         self.io.read_exact(&mut b)?;
@@ -65,3 +186,67 @@ impl<'a, T: Read + Seek> Fat<'a, T> {
         Ok(out)
     }
 }
+
+/// A FAT entry whose value differs between the active and inactive FAT.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatEntryDiff {
+    pub cluster: u32,
+    pub active: u32,
+    pub inactive: u32,
+}
+
+/// A cluster whose allocation bit differs between the active and inactive bitmap.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BitmapBitDiff {
+    pub cluster: u32,
+    pub active: bool,
+    pub inactive: bool,
+}
+
+/// Differences between the two FATs and bitmaps of a TexFAT volume,
+/// i.e. the footprint of transactions that were not committed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TexFatDiff {
+    pub active_fat: u8,
+    pub fat_entries: Vec<FatEntryDiff>,
+    pub bitmap_bits: Vec<BitmapBitDiff>,
+}
+
+impl TexFatDiff {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for TexFatDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Active FAT: {}", self.active_fat)?;
+        if self.fat_entries.is_empty() && self.bitmap_bits.is_empty() {
+            return writeln!(f, "Active and inactive FAT/bitmap are identical.");
+        }
+        let mut t = Table::new();
+        t.add_row(Row::new(vec![
+            Cell::new("Cluster"),
+            Cell::new("Structure"),
+            Cell::new("Active"),
+            Cell::new("Inactive"),
+        ]));
+        for d in &self.fat_entries {
+            t.add_row(Row::new(vec![
+                Cell::new(&d.cluster.to_string()),
+                Cell::new("FAT"),
+                Cell::new(&format!("0x{:08X}", d.active)),
+                Cell::new(&format!("0x{:08X}", d.inactive)),
+            ]));
+        }
+        for d in &self.bitmap_bits {
+            t.add_row(Row::new(vec![
+                Cell::new(&d.cluster.to_string()),
+                Cell::new("Bitmap"),
+                Cell::new(&d.active.to_string()),
+                Cell::new(&d.inactive.to_string()),
+            ]));
+        }
+        write!(f, "{}", t)
+    }
+}
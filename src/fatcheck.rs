@@ -0,0 +1,380 @@
+use crate::direntry::{AllocationBitmapEntry, EntryType, UpcaseTableEntry};
+use crate::fat::is_eoc;
+use crate::fs::{ExFatFS, FsError};
+use log::debug;
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Read, Seek};
+
+/// FAT value marking a bad cluster.
+pub const FAT_BAD: u32 = 0xFFFF_FFF7;
+
+/// A FAT entry holding a value that is not a valid link.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatEntryValue {
+    pub cluster: u32,
+    pub value: u32,
+}
+
+/// A cluster claimed by more than one chain (owners are the chain heads).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrossLink {
+    pub cluster: u32,
+    pub owners: Vec<u32>,
+}
+
+/// A FAT chain, in order from its head.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatChain {
+    pub head: u32,
+    pub clusters: Vec<u32>,
+    pub terminated: bool, // ends with an end-of-chain marker
+    pub looped: bool,     // runs back into itself
+}
+
+/// Result of checking every entry of the active FAT.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FatReport {
+    pub active_fat: u8,
+    pub cluster_count: u32,
+    pub free_entries: u64,
+    pub used_entries: u64,
+    pub bad_clusters: Vec<u32>,
+    pub out_of_range: Vec<FatEntryValue>,
+    pub reserved_values: Vec<FatEntryValue>,
+    pub cross_links: Vec<CrossLink>,
+    pub cycles: Vec<u32>, // one cluster per loop
+    pub orphan_chains: Vec<FatChain>,
+}
+
+impl FatReport {
+    pub fn is_clean(&self) -> bool {
+        self.bad_clusters.is_empty()
+            && self.out_of_range.is_empty()
+            && self.reserved_values.is_empty()
+            && self.cross_links.is_empty()
+            && self.cycles.is_empty()
+            && self.orphan_chains.is_empty()
+    }
+
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for FatReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut t = Table::new();
+        for (k, v) in [
+            ("Active FAT", self.active_fat.to_string()),
+            ("Clusters", self.cluster_count.to_string()),
+            ("Free entries", self.free_entries.to_string()),
+            ("Used entries", self.used_entries.to_string()),
+            ("Bad clusters", self.bad_clusters.len().to_string()),
+            ("Out-of-range links", self.out_of_range.len().to_string()),
+            ("Reserved values", self.reserved_values.len().to_string()),
+            ("Cross-linked clusters", self.cross_links.len().to_string()),
+            ("Cycles", self.cycles.len().to_string()),
+            ("Orphan chains", self.orphan_chains.len().to_string()),
+        ] {
+            t.add_row(Row::new(vec![Cell::new(k), Cell::new(&v)]));
+        }
+        writeln!(f, "{}", t)?;
+
+        if self.is_clean() {
+            return Ok(());
+        }
+        let mut d = Table::new();
+        d.add_row(Row::new(vec![
+            Cell::new("Cluster"),
+            Cell::new("Anomaly"),
+            Cell::new("Detail"),
+        ]));
+        let mut row = |c: u32, kind: &str, detail: String| {
+            d.add_row(Row::new(vec![
+                Cell::new(&c.to_string()),
+                Cell::new(kind),
+                Cell::new(&detail),
+            ]));
+        };
+        for &c in &self.bad_clusters {
+            row(c, "bad", format!("0x{:08X}", FAT_BAD));
+        }
+        for e in &self.out_of_range {
+            row(e.cluster, "out of range", format!("-> 0x{:08X}", e.value));
+        }
+        for e in &self.reserved_values {
+            row(e.cluster, "reserved value", format!("0x{:08X}", e.value));
+        }
+        for x in &self.cross_links {
+            let owners: Vec<String> = x.owners.iter().map(|o| o.to_string()).collect();
+            row(
+                x.cluster,
+                "cross-linked",
+                format!("chains {}", owners.join(", ")),
+            );
+        }
+        for &c in &self.cycles {
+            row(c, "cycle", String::new());
+        }
+        for ch in &self.orphan_chains {
+            row(
+                ch.head,
+                "orphan chain",
+                format!(
+                    "{} clusters{}",
+                    ch.clusters.len(),
+                    if ch.terminated { "" } else { ", unterminated" }
+                ),
+            );
+        }
+        write!(f, "{}", d)
+    }
+}
+
+/// Follow `head` through an in-memory FAT. Stops at EOC, at any invalid link or on a loop.
+pub(crate) fn follow(fat: &[u32], head: u32) -> FatChain {
+    let last = fat.len() as u32; // one past the last valid cluster
+    let mut clusters = Vec::new();
+    let mut seen = HashSet::new();
+    let mut cur = head;
+    let mut terminated = false;
+    let mut looped = false;
+    while (2..last).contains(&cur) {
+        if !seen.insert(cur) {
+            looped = true;
+            break;
+        }
+        clusters.push(cur);
+        let next = fat[cur as usize];
+        if is_eoc(next) {
+            terminated = true;
+            break;
+        }
+        cur = next;
+    }
+    FatChain {
+        head,
+        clusters,
+        terminated,
+        looped,
+    }
+}
+
+/// A link to another data cluster.
+#[inline]
+fn is_link(v: u32, last: u32) -> bool {
+    (2..last).contains(&v)
+}
+
+/// Allocations of live directory entries (metadata, directories and files) as chains.
+/// Contiguous (NoFatChain) allocations are expanded without the FAT.
+fn referenced_allocations<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    fat: &[u32],
+) -> Result<Vec<FatChain>, FsError> {
+    let bpc = fs.bpb.bytes_per_cluster();
+    let root = fs.bpb.root_dir_first_cluster;
+    let mut out = vec![follow(fat, root)];
+
+    let raw = fs.read_dir_entries_from_chain(root)?;
+    for e in raw.iter().take_while(|e| e.kind() != EntryType::End) {
+        let first = match e.kind() {
+            EntryType::AllocationBitmap => AllocationBitmapEntry::parse(e).first_cluster,
+            EntryType::UpCaseTable => UpcaseTableEntry::parse(e).first_cluster,
+            _ => continue,
+        };
+        out.push(follow(fat, first));
+    }
+
+    let alloc = |first: u32, size: u64, flags: u8| {
+        if flags & 0x02 != 0 {
+            let n = size.div_ceil(bpc).max(1) as u32;
+            let end = first.saturating_add(n).min(fat.len() as u32);
+            FatChain {
+                head: first,
+                clusters: (first..end).collect(),
+                terminated: true,
+                looped: false,
+            }
+        } else {
+            follow(fat, first)
+        }
+    };
+    for (_ino, _parent, fr) in fs.indexed_records()? {
+        // vendor allocations belong to the set, whatever its own allocation
+        for va in fr
+            .vendor_allocations
+            .iter()
+            .filter(|va| va.first_cluster >= 2)
+        {
+            out.push(alloc(va.first_cluster, va.data_length, va.general_flags));
+        }
+        if fr.first_cluster >= 2 {
+            out.push(alloc(fr.first_cluster, fr.size, fr.general_flags));
+        }
+    }
+    Ok(out)
+}
+
+/// Chains whose head is in use but neither referenced by a live entry nor linked to
+/// from another FAT entry.
+fn orphans(fat: &[u32], has_pred: &[bool], heads: &HashSet<u32>, owned: &[bool]) -> Vec<FatChain> {
+    (2..fat.len() as u32)
+        .filter(|&c| {
+            let v = fat[c as usize];
+            v != 0
+                && v != FAT_BAD
+                && !has_pred[c as usize]
+                && !heads.contains(&c)
+                && !owned[c as usize]
+        })
+        .map(|c| follow(fat, c))
+        .collect()
+}
+
+/// FAT chains no live directory entry points to (e.g. files deleted by Windows, which
+/// clears the bitmap but leaves the FAT), one per head cluster.
+pub fn lost_chains<T: Read + Seek>(fs: &mut ExFatFS<T>) -> Result<Vec<FatChain>, FsError> {
+    let fat = fs.read_fat(fs.bpb.active_fat())?;
+    let last = fat.len() as u32;
+    let mut has_pred = vec![false; fat.len()];
+    for &v in &fat[2..] {
+        if is_link(v, last) {
+            has_pred[v as usize] = true;
+        }
+    }
+    let referenced = referenced_allocations(fs, &fat)?;
+    let mut owned = vec![false; fat.len()];
+    for ch in &referenced {
+        for &c in &ch.clusters {
+            owned[c as usize] = true;
+        }
+    }
+    let heads = referenced.iter().map(|ch| ch.head).collect();
+    Ok(orphans(&fat, &has_pred, &heads, &owned))
+}
+
+/// Check every entry of the active FAT: bad clusters, out-of-range and reserved values,
+/// cycles, clusters claimed by more than one chain, and chains no directory entry references.
+pub fn analyze_fat<T: Read + Seek>(fs: &mut ExFatFS<T>) -> Result<FatReport, FsError> {
+    let active = fs.bpb.active_fat();
+    let fat = fs.read_fat(active)?;
+    let last = fat.len() as u32;
+
+    let mut free_entries = 0u64;
+    let mut used_entries = 0u64;
+    let mut bad_clusters = Vec::new();
+    let mut out_of_range = Vec::new();
+    let mut reserved_values = Vec::new();
+    let mut has_pred = vec![false; fat.len()];
+
+    for c in 2..last {
+        let v = fat[c as usize];
+        match v {
+            0 => free_entries += 1,
+            FAT_BAD => bad_clusters.push(c),
+            0xFFFF_FFFF => used_entries += 1,
+            _ if is_eoc(v) => {
+                // 0xFFFFFFF8..=0xFFFFFFFE: media descriptor values, not defined for exFAT
+                used_entries += 1;
+                reserved_values.push(FatEntryValue {
+                    cluster: c,
+                    value: v,
+                });
+            }
+            _ if is_link(v, last) => {
+                used_entries += 1;
+                has_pred[v as usize] = true;
+            }
+            _ => {
+                used_entries += 1;
+                out_of_range.push(FatEntryValue {
+                    cluster: c,
+                    value: v,
+                });
+            }
+        }
+    }
+
+    // ownership of every cluster by live allocations
+    let referenced = referenced_allocations(fs, &fat)?;
+    let mut owners: BTreeMap<u32, Vec<u32>> = BTreeMap::new();
+    let mut owned = vec![false; fat.len()];
+    let mut heads: HashSet<u32> = HashSet::new();
+    let mut cycles = Vec::new();
+    for ch in &referenced {
+        heads.insert(ch.head);
+        if ch.looped {
+            cycles.push(ch.head);
+        }
+        for &c in &ch.clusters {
+            owned[c as usize] = true;
+            owners.entry(c).or_default().push(ch.head);
+        }
+    }
+
+    let orphan_chains = orphans(&fat, &has_pred, &heads, &owned);
+    for ch in &orphan_chains {
+        if ch.looped {
+            cycles.push(ch.head);
+        }
+        for &x in &ch.clusters {
+            owners.entry(x).or_default().push(ch.head);
+        }
+    }
+
+    // whatever is in use but reachable from no head can only sit on a loop
+    let mut reached = owned;
+    for ch in &orphan_chains {
+        for &x in &ch.clusters {
+            reached[x as usize] = true;
+        }
+    }
+    let mut on_loop = vec![false; fat.len()];
+    for c in 2..last {
+        let v = fat[c as usize];
+        if reached[c as usize] || on_loop[c as usize] || !(is_link(v, last) || is_eoc(v)) {
+            continue;
+        }
+        let ch = follow(&fat, c);
+        for &x in &ch.clusters {
+            on_loop[x as usize] = true;
+        }
+        if ch.looped {
+            cycles.push(c);
+        }
+    }
+
+    cycles.sort_unstable();
+    cycles.dedup();
+    let cross_links = owners
+        .into_iter()
+        .filter_map(|(cluster, mut o)| {
+            o.sort_unstable();
+            o.dedup();
+            (o.len() > 1).then_some(CrossLink { cluster, owners: o })
+        })
+        .collect();
+
+    debug!(
+        "analyze_fat: {} used, {} orphan chains",
+        used_entries,
+        orphan_chains.len()
+    );
+
+    Ok(FatReport {
+        active_fat: active,
+        cluster_count: fs.bpb.cluster_count,
+        free_entries,
+        used_entries,
+        bad_clusters,
+        out_of_range,
+        reserved_values,
+        cross_links,
+        cycles,
+        orphan_chains,
+    })
+}
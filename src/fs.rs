@@ -1,9 +1,20 @@
-use crate::bpb::BootSector;
+use crate::bitmap::AllocationBitmap;
+use crate::bootcode::{BootCodeReport, KNOWN_BOOT_CODE, KnownBootCode};
+use crate::bpb::{
+    BOOT_REGION_SECTORS, BootChecksumVerdict, BootDiff, BootSector, ExtendedBootSector,
+    OemParameters, PercentInUseCheck,
+};
 use crate::compat::CompatDirEntry;
-use crate::direntry::{EntryType, FileRecord, RawDirEnt, assemble_file};
+use crate::direntry::{
+    AllocationBitmapEntry, EntryType, FileAttributes, FileRecord, RawDirEnt, UpcaseTableEntry,
+    VendorAllocationEntry, VolumeGuidEntry, VolumeLabelEntry, assemble_file,
+};
 use crate::exinode::ExInode;
-use crate::fat::Fat;
-use log::{debug, error, warn};
+use crate::fat::{BitmapBitDiff, DEFAULT_FAT_CACHE_BYTES, Fat, FatCache, FatEntryDiff, TexFatDiff};
+use crate::geometry::{check_geometry, infer_geometry};
+use crate::upcase::UpcaseTable;
+use crate::walk::{WalkEntry, WalkOptions, WalkStats};
+use log::{debug, warn};
 use serde_json::{Value, json};
 use std::collections::HashMap;
 use std::io::{Read, Seek, SeekFrom};
@@ -21,28 +32,254 @@ pub enum FsError {
     NotAFile(String),
 }
 
+/// Open-time behaviour of [`ExFatFS`].
+#[derive(Debug, Clone)]
+pub struct ExFatOptions {
+    /// Open from the backup boot region (sectors 12..=23) when the main one fails validation.
+    pub backup_boot_fallback: bool,
+    /// Record boot sector violations as warnings instead of failing, and infer
+    /// missing geometry from the FAT and root directory. Opening still fails if the
+    /// heap, cluster count or root cluster cannot be recovered.
+    pub lenient: bool,
+    /// Memory budget of the FAT cache shared by all chain walks (0 disables it).
+    pub fat_cache_bytes: usize,
+}
+
+impl Default for ExFatOptions {
+    fn default() -> Self {
+        Self {
+            backup_boot_fallback: false,
+            lenient: false,
+            fat_cache_bytes: DEFAULT_FAT_CACHE_BYTES,
+        }
+    }
+}
+
+/// Read the 12 sectors of a boot region starting at `first_sector`.
+fn read_boot_region<T: Read + Seek>(
+    io: &mut T,
+    first_sector: u64,
+    bps: usize,
+) -> std::io::Result<Vec<u8>> {
+    let mut region = vec![0u8; bps * BOOT_REGION_SECTORS as usize];
+    io.seek(SeekFrom::Start(first_sector * bps as u64))?;
+    io.read_exact(&mut region)?;
+    Ok(region)
+}
+
+/// Locate and parse the backup boot region without help from the main one:
+/// every legal sector size is tried and the BPB must agree with the one used to find it.
+fn find_backup_boot_region<T: Read + Seek>(io: &mut T) -> Option<BootSector> {
+    for shift in 9u8..=12 {
+        let bps = 1usize << shift;
+        let region = match read_boot_region(io, BOOT_REGION_SECTORS, bps) {
+            Ok(r) => r,
+            Err(_) => continue,
+        };
+        match BootSector::from_bytes(&region) {
+            Ok(bpb) if bpb.bytes_per_sector_shift == shift => return Some(bpb),
+            Ok(_) => continue,
+            Err(e) => debug!("backup boot sector @{} bytes rejected: {}", bps * 12, e),
+        }
+    }
+    None
+}
+
+/// Bytes between ValidDataLength and DataLength are undefined on disk and read as zeros.
+fn zero_past_valid_data(data: &mut [u8], fr: &FileRecord) {
+    if !fr.is_dir() && fr.valid_data_length < data.len() as u64 {
+        data[fr.valid_data_length as usize..].fill(0);
+    }
+}
+
+/// Name of the virtual directory holding recovered lost chains (child of the root).
+pub const LOST_CHAINS_DIR: &str = "$LostChains";
+
+/// Pseudo first cluster of the lost-chains directory; cluster 1 never holds data,
+/// so its children get inode `(1 << 32) | head_cluster`.
+pub const LOST_CHAINS_CLUSTER: u32 = 1;
+
+/// Index entries made up by `recover_lost_chains`: the `$LostChains` directory and its
+/// children have no entry set on disk.
+fn is_synthetic(parent: u32, idx: usize) -> bool {
+    idx == usize::MAX || parent == LOST_CHAINS_CLUSTER
+}
+
 pub struct ExFatFS<T: Read + Seek> {
     pub bpb: BootSector,
     pub io: T,
+    pub boot_checksum: BootChecksumVerdict,
+    // backup boot region (sectors 12..=23), if it could be parsed
+    pub backup_bpb: Option<BootSector>,
+    pub backup_checksum: Option<BootChecksumVerdict>,
+    pub opened_from_backup: bool,
+    // spec violations tolerated (lenient mode) and geometry inferred while opening
+    pub warnings: Vec<String>,
+    // raw main boot region (sectors 0..=11)
+    boot_region: Vec<u8>,
+    fat_cache: FatCache,
     // fake-inode index: inode -> (parent_dir_first_cluster, primary_entry_index, FileRecord)
     index_built: bool,
     inode_to_record: HashMap<u64, (u32, usize, FileRecord)>,
+    // directory first cluster -> its inode, for path reconstruction
+    dir_inodes: HashMap<u32, u64>,
+    // up-case table for name comparison, loaded on first use
+    upcase: Option<UpcaseTable>,
 }
 
 impl<T: Read + Seek> ExFatFS<T> {
-    pub fn new(mut io: T) -> Result<Self, FsError> {
+    pub fn new(io: T) -> Result<Self, FsError> {
+        Self::new_with_options(io, ExFatOptions::default())
+    }
+
+    pub fn new_with_options(mut io: T, opts: ExFatOptions) -> Result<Self, FsError> {
         io.seek(SeekFrom::Start(0))?;
         let mut b = [0u8; 512];
         io.read_exact(&mut b)?;
-        let bpb = BootSector::from_bytes(&b).map_err(FsError::Parse)?;
+
+        let (bpb, opened_from_backup, warnings) = match BootSector::from_bytes(&b) {
+            Ok(bpb) => (bpb, false, Vec::new()),
+            Err(e) => {
+                let backup = if opts.backup_boot_fallback {
+                    warn!(
+                        "Main boot sector invalid ({}); trying the backup boot region",
+                        e
+                    );
+                    find_backup_boot_region(&mut io)
+                } else {
+                    None
+                };
+                match backup {
+                    Some(bpb) => (bpb, true, vec![format!("main boot sector invalid: {}", e)]),
+                    None if opts.lenient => {
+                        let (mut bpb, mut warnings) =
+                            BootSector::from_bytes_lenient(&b).map_err(FsError::Parse)?;
+                        infer_geometry(&mut io, &mut bpb, &mut warnings);
+                        check_geometry(&bpb).map_err(FsError::Parse)?;
+                        (bpb, false, warnings)
+                    }
+                    None if opts.backup_boot_fallback => {
+                        return Err(FsError::Parse(format!(
+                            "{} (backup boot region invalid too)",
+                            e
+                        )));
+                    }
+                    None => return Err(FsError::Parse(e)),
+                }
+            }
+        };
+        let first_sector = if opened_from_backup {
+            BOOT_REGION_SECTORS
+        } else {
+            0
+        };
+        let boot_region = read_boot_region(&mut io, first_sector, bpb.bytes_per_sector() as usize)?;
+
+        let bps = bpb.bytes_per_sector() as usize;
+        let boot_checksum =
+            BootChecksumVerdict::verify(&boot_region, bps).map_err(FsError::Parse)?;
+
+        let (backup_bpb, backup_checksum) = if opened_from_backup {
+            (Some(bpb.clone()), Some(boot_checksum.clone()))
+        } else {
+            match read_boot_region(&mut io, BOOT_REGION_SECTORS, bps) {
+                Ok(region) => match BootSector::from_bytes(&region) {
+                    Ok(b) => (Some(b), BootChecksumVerdict::verify(&region, bps).ok()),
+                    Err(e) => {
+                        warn!("Backup boot sector invalid: {}", e);
+                        (None, BootChecksumVerdict::verify(&region, bps).ok())
+                    }
+                },
+                Err(e) => {
+                    warn!("Backup boot region unreadable: {}", e);
+                    (None, None)
+                }
+            }
+        };
+
         Ok(Self {
             bpb,
             io,
+            boot_checksum,
+            backup_bpb,
+            backup_checksum,
+            opened_from_backup,
+            warnings,
+            boot_region,
+            fat_cache: FatCache::new(opts.fat_cache_bytes),
             index_built: false,
             inode_to_record: HashMap::new(),
+            dir_inodes: HashMap::new(),
+            upcase: None,
         })
     }
 
+    /// Reader over the active FAT, backed by the volume's FAT cache.
+    pub fn fat(&mut self) -> Fat<'_, T> {
+        Fat::with_cache(&self.bpb, &mut self.io, &mut self.fat_cache)
+    }
+
+    pub fn fat_cache(&self) -> &FatCache {
+        &self.fat_cache
+    }
+
+    /// Raw bytes of the main boot region (12 sectors).
+    pub fn boot_region(&self) -> &[u8] {
+        &self.boot_region
+    }
+
+    /// Extended boot sectors (sectors 1..=8 of the boot region in use).
+    pub fn extended_boot_sectors(&self) -> Vec<ExtendedBootSector> {
+        ExtendedBootSector::all_from_region(&self.boot_region, self.bpb.bytes_per_sector() as usize)
+    }
+
+    /// OEM parameters (sector 9 of the boot region in use).
+    pub fn oem_parameters(&self) -> OemParameters {
+        let bps = self.bpb.bytes_per_sector() as usize;
+        OemParameters::from_bytes(&self.boot_region[9 * bps..10 * bps])
+    }
+
+    /// Hash the boot code and extended boot sectors and match them against known formatters.
+    pub fn boot_code_report(&self) -> BootCodeReport {
+        BootCodeReport::analyze(
+            &self.bpb.boot_code,
+            &self.boot_region,
+            self.bpb.bytes_per_sector() as usize,
+        )
+    }
+
+    /// `boot_code_report` with extra reference images, tried before the built-in table.
+    pub fn boot_code_report_with(&self, extra: &[KnownBootCode]) -> BootCodeReport {
+        let known: Vec<KnownBootCode> = extra.iter().chain(KNOWN_BOOT_CODE).cloned().collect();
+        BootCodeReport::analyze_with(
+            &self.bpb.boot_code,
+            &self.boot_region,
+            self.bpb.bytes_per_sector() as usize,
+            &known,
+        )
+    }
+
+    /// Compare PercentInUse with the number of clusters set in the allocation bitmap.
+    pub fn percent_in_use_check(&mut self) -> Result<PercentInUseCheck, FsError> {
+        let used = self.allocation_bitmap()?.used_clusters();
+        Ok(PercentInUseCheck::new(
+            self.bpb.percent_in_use,
+            used,
+            self.bpb.cluster_count,
+        ))
+    }
+
+    /// Compare the main boot sector with the backup one.
+    /// `None` if the backup is unusable or the volume was opened from it (no main to compare).
+    pub fn boot_diff(&self) -> Option<BootDiff> {
+        if self.opened_from_backup {
+            return None;
+        }
+        self.backup_bpb
+            .as_ref()
+            .map(|backup| BootDiff::between(&self.bpb, backup))
+    }
+
     #[inline]
     pub fn bytes_per_sector(&self) -> u64 {
         self.bpb.bytes_per_sector()
@@ -67,31 +304,252 @@ impl<T: Read + Seek> ExFatFS<T> {
         &mut self,
         first_cluster: u32,
     ) -> Result<Vec<RawDirEnt>, FsError> {
-        let mut fat = Fat::new(&self.bpb, &mut self.io);
+        let mut fat = self.fat();
         let chain = fat.walk_chain(first_cluster, 1_000_000)?;
         debug!(
             "read_dir_entries_from_chain: first_cluster={} chain_len={}",
             first_cluster,
             chain.len()
         );
+        self.dir_entries_of(&chain)
+    }
+
+    /// The root directory as a record: FAT chained, no DataLength (read to the end of its chain).
+    pub fn root_dir_record(&self) -> FileRecord {
+        FileRecord {
+            name: "/".to_string(),
+            attributes: FileAttributes::DIRECTORY,
+            first_cluster: self.bpb.root_dir_first_cluster,
+            general_flags: 0x01,
+            ..Default::default()
+        }
+    }
+
+    /// Read the entries of directory `dir` as its Stream Extension describes it:
+    /// contiguous clusters when NoFatChain is set, otherwise its FAT chain, in both
+    /// cases limited to DataLength (a zero DataLength follows the chain to its end).
+    pub fn read_dir_entries(&mut self, dir: &FileRecord) -> Result<Vec<RawDirEnt>, FsError> {
+        let bpc = self.bpb.bytes_per_cluster();
+        let clusters = dir.size.div_ceil(bpc) as usize;
+        let chain: Vec<u32> = if dir.general_flags & 0x02 != 0 {
+            let last = self.bpb.cluster_count + 2;
+            (dir.first_cluster..last).take(clusters.max(1)).collect()
+        } else {
+            let max = if clusters > 0 { clusters } else { 1_000_000 };
+            self.fat().walk_chain(dir.first_cluster, max)?
+        };
+        debug!(
+            "read_dir_entries: '{}' first_cluster={} no_fat_chain={} clusters={}",
+            dir.name,
+            dir.first_cluster,
+            dir.general_flags & 0x02 != 0,
+            chain.len()
+        );
+        self.dir_entries_of(&chain)
+    }
+
+    fn dir_entries_of(&mut self, clusters: &[u32]) -> Result<Vec<RawDirEnt>, FsError> {
         let mut out = Vec::new();
-        for cl in chain {
+        for &cl in clusters {
             let buf = self.read_cluster(cl)?;
-            for chunk in buf.chunks(32) {
+            let base = self.cluster_to_offset(cl);
+            for (i, chunk) in buf.chunks(32).enumerate() {
                 if chunk.len() < 32 {
                     break;
                 }
-                out.push(RawDirEnt::from_bytes(chunk));
+                out.push(RawDirEnt::from_bytes_at(chunk, base + i as u64 * 32));
             }
         }
         Ok(out)
     }
 
+    /// Read FAT number `index` (0 or 1) as `cluster_count + 2` entries, capped at the FAT's size.
+    pub fn read_fat(&mut self, index: u8) -> Result<Vec<u32>, FsError> {
+        if index >= self.bpb.num_fats {
+            return Err(FsError::NotFound(format!(
+                "FAT #{} (volume has {})",
+                index, self.bpb.num_fats
+            )));
+        }
+        // never past the FAT itself, whatever ClusterCount claims
+        let fat_bytes = self.bpb.fat_length as u64 * self.bpb.bytes_per_sector();
+        let len = ((self.bpb.cluster_count as u64 + 2) * 4).min(fat_bytes);
+        self.io
+            .seek(SeekFrom::Start(self.bpb.fat_start_byte_of(index)))?;
+        // in 1 MiB chunks, so a FAT claimed larger than the image fails on the first
+        // short read instead of allocating the claimed size up front
+        let mut raw = Vec::new();
+        let mut chunk = vec![0u8; 1 << 20];
+        while (raw.len() as u64) < len {
+            let n = chunk.len().min((len - raw.len() as u64) as usize);
+            self.io.read_exact(&mut chunk[..n])?;
+            raw.extend_from_slice(&chunk[..n]);
+        }
+        Ok(raw
+            .chunks_exact(4)
+            .map(|c| u32::from_le_bytes([c[0], c[1], c[2], c[3]]))
+            .collect())
+    }
+
+    /// Find the Allocation Bitmap entry (0x81) for bitmap `index` (BitmapFlags bit 0)
+    /// in the root directory and read it into memory.
+    pub fn read_allocation_bitmap(&mut self, index: u8) -> Result<AllocationBitmap, FsError> {
+        let raw = self.read_dir_entries_from_chain(self.bpb.root_dir_first_cluster)?;
+        let single = self.bpb.num_fats < 2;
+        let entry = raw
+            .iter()
+            .take_while(|e| e.kind() != EntryType::End)
+            .filter(|e| e.kind() == EntryType::AllocationBitmap)
+            .map(AllocationBitmapEntry::parse)
+            .find(|b| single || (b.bitmap_flags & 0x01) == index)
+            .ok_or_else(|| {
+                FsError::NotFound(format!("Allocation Bitmap #{} not found in root", index))
+            })?;
+        let buf = self.read_chained(entry.first_cluster, entry.data_length as usize)?;
+        Ok(AllocationBitmap::new(index, self.bpb.cluster_count, buf))
+    }
+
+    /// Read `len` bytes following the FAT chain from `first_cluster` (metadata files).
+    fn read_chained(&mut self, first_cluster: u32, len: usize) -> Result<Vec<u8>, FsError> {
+        let max = (len as u64).div_ceil(self.bpb.bytes_per_cluster()) as usize + 1;
+        let chain = self.fat().walk_chain(first_cluster, max)?;
+        let mut buf = Vec::with_capacity(len);
+        for cl in chain {
+            let block = self.read_cluster(cl)?;
+            buf.extend_from_slice(&block);
+            if buf.len() >= len {
+                break;
+            }
+        }
+        buf.truncate(len);
+        Ok(buf)
+    }
+
+    /// Find the Up-case Table entry (0x82) in the root directory, read and expand the table.
+    pub fn read_upcase_table(&mut self) -> Result<UpcaseTable, FsError> {
+        let raw = self.read_dir_entries_from_chain(self.bpb.root_dir_first_cluster)?;
+        let entry = raw
+            .iter()
+            .take_while(|e| e.kind() != EntryType::End)
+            .find(|e| e.kind() == EntryType::UpCaseTable)
+            .map(UpcaseTableEntry::parse)
+            .ok_or_else(|| FsError::NotFound("Up-case Table entry not found in root".into()))?;
+        let data = self.read_chained(entry.first_cluster, entry.data_length as usize)?;
+        Ok(UpcaseTable::from_bytes(&data, entry.table_checksum))
+    }
+
+    /// The volume label (0x83 entry of the root directory), if set.
+    pub fn volume_label(&mut self) -> Result<Option<String>, FsError> {
+        let raw = self.read_dir_entries_from_chain(self.bpb.root_dir_first_cluster)?;
+        Ok(raw
+            .iter()
+            .take_while(|e| e.kind() != EntryType::End)
+            .find(|e| e.kind() == EntryType::VolumeLabel)
+            .map(|e| VolumeLabelEntry::parse(e).label)
+            .filter(|l| !l.is_empty()))
+    }
+
+    /// The Volume GUID entry (0xA0) of the root directory, if present.
+    pub fn volume_guid(&mut self) -> Result<Option<VolumeGuidEntry>, FsError> {
+        let raw = self.read_dir_entries_from_chain(self.bpb.root_dir_first_cluster)?;
+        Ok(raw
+            .iter()
+            .take_while(|e| e.kind() != EntryType::End)
+            .find(|e| e.kind() == EntryType::VolumeGuid)
+            .map(VolumeGuidEntry::parse))
+    }
+
+    /// Read the clusters of a Vendor Allocation entry (0xE1), FAT-chained or contiguous.
+    pub fn read_vendor_allocation(
+        &mut self,
+        va: &VendorAllocationEntry,
+    ) -> Result<Vec<u8>, FsError> {
+        let fr = FileRecord {
+            name: format!("vendor allocation {}", va.vendor_guid),
+            first_cluster: va.first_cluster,
+            size: va.data_length,
+            valid_data_length: va.data_length,
+            general_flags: va.general_flags,
+            ..Default::default()
+        };
+        self.read_file(&fr)
+    }
+
+    /// The up-case table used for name comparison, loaded on first use. Falls back to
+    /// ASCII-only case folding if the volume's table cannot be read.
+    pub fn upcase_table(&mut self) -> &UpcaseTable {
+        if self.upcase.is_none() {
+            let table = self.read_upcase_table().unwrap_or_else(|e| {
+                warn!("up-case table unreadable ({}); using ASCII case folding", e);
+                UpcaseTable::ascii()
+            });
+            for msg in table.findings() {
+                warn!("{}", msg);
+            }
+            self.upcase = Some(table);
+        }
+        self.upcase.as_ref().unwrap()
+    }
+
+    /// The allocation bitmap in use (selected by ActiveFat on TexFAT volumes).
+    pub fn allocation_bitmap(&mut self) -> Result<AllocationBitmap, FsError> {
+        self.read_allocation_bitmap(self.bpb.active_fat())
+    }
+
+    /// Compare the active FAT and bitmap with the inactive ones (TexFAT volumes only).
+    pub fn texfat_diff(&mut self) -> Result<TexFatDiff, FsError> {
+        if self.bpb.num_fats < 2 {
+            return Err(FsError::NotFound("second FAT (not a TexFAT volume)".into()));
+        }
+        let active = self.bpb.active_fat();
+        let inactive = 1 - active;
+
+        let (fa, fi) = (self.read_fat(active)?, self.read_fat(inactive)?);
+        let fat_entries = (2..fa.len())
+            .filter(|&c| fa[c] != fi[c])
+            .map(|c| FatEntryDiff {
+                cluster: c as u32,
+                active: fa[c],
+                inactive: fi[c],
+            })
+            .collect();
+
+        let (ba, bi) = (
+            self.read_allocation_bitmap(active)?,
+            self.read_allocation_bitmap(inactive)?,
+        );
+        let bitmap_bits = (2..self.bpb.cluster_count + 2)
+            .filter(|&c| ba.is_allocated(c) != bi.is_allocated(c))
+            .map(|c| BitmapBitDiff {
+                cluster: c,
+                active: ba.is_allocated(c),
+                inactive: bi.is_allocated(c),
+            })
+            .collect();
+
+        Ok(TexFatDiff {
+            active_fat: active,
+            fat_entries,
+            bitmap_bits,
+        })
+    }
+
     pub fn list_dir_with_inodes(
         &mut self,
         first_cluster: u32,
     ) -> Result<Vec<(u64, FileRecord)>, FsError> {
-        let ents = self.read_dir_entries_from_chain(first_cluster)?;
+        let dir = self.dir_record(first_cluster)?;
+        self.list_dir_record_with_inodes(&dir)
+    }
+
+    /// Entry sets of directory `dir` with their fake inode numbers, read as its own
+    /// record describes it (NoFatChain, DataLength).
+    pub fn list_dir_record_with_inodes(
+        &mut self,
+        dir: &FileRecord,
+    ) -> Result<Vec<(u64, FileRecord)>, FsError> {
+        let first_cluster = dir.first_cluster;
+        let ents = self.read_dir_entries(dir)?;
         let mut out = Vec::new();
         let mut i = 0usize;
         while i < ents.len() {
@@ -117,11 +575,18 @@ impl<T: Read + Seek> ExFatFS<T> {
 
     /// Convenience for the root directory.
     pub fn list_root_with_inodes(&mut self) -> Result<Vec<(u64, FileRecord)>, FsError> {
-        self.list_dir_with_inodes(self.bpb.root_dir_first_cluster)
+        let root = self.root_dir_record();
+        self.list_dir_record_with_inodes(&root)
     }
 
     pub fn list_dir(&mut self, first_cluster: u32) -> Result<Vec<FileRecord>, FsError> {
-        let ents = self.read_dir_entries_from_chain(first_cluster)?;
+        let dir = self.dir_record(first_cluster)?;
+        self.list_dir_record(&dir)
+    }
+
+    /// Entry sets of directory `dir`, read as its own record describes it.
+    pub fn list_dir_record(&mut self, dir: &FileRecord) -> Result<Vec<FileRecord>, FsError> {
+        let ents = self.read_dir_entries(dir)?;
         let mut out = Vec::new();
         let mut i = 0usize;
         while i < ents.len() {
@@ -150,35 +615,32 @@ impl<T: Read + Seek> ExFatFS<T> {
             return Ok(());
         }
         self.inode_to_record.clear();
-        let mut stack: Vec<u32> = vec![self.bpb.root_dir_first_cluster];
-
-        while let Some(dir_clus) = stack.pop() {
-            let ents = self.read_dir_entries_from_chain(dir_clus)?;
-            let mut i = 0usize;
-            while i < ents.len() {
-                match ents[i].kind() {
-                    EntryType::End => break,
-                    EntryType::File => {
-                        let sec_cnt = ents[i].raw[1] as usize;
-                        let end = (i + 1 + sec_cnt).min(ents.len());
-                        if let Some(fr) = assemble_file(&ents[i..end]) {
-                            let ino = ((dir_clus as u64) << 32) | (i as u64);
-                            self.inode_to_record.insert(ino, (dir_clus, i, fr.clone()));
-                            if fr.is_dir() && fr.first_cluster >= 2 {
-                                stack.push(fr.first_cluster);
-                            }
-                        } else {
-                            warn!(
-                                "assemble_file failed while indexing (dir_cluster={}, i={})",
-                                dir_clus, i
-                            );
-                        }
-                        i = end;
-                        continue;
-                    }
-                    _ => i += 1,
-                }
+        self.dir_inodes.clear();
+        let mut found = Vec::new();
+        self.walk(&WalkOptions::default(), |e| {
+            found.push(e);
+            Ok(())
+        })?;
+        for e in found {
+            let (ino, fr) = (e.inode, e.record);
+            if !fr.set_checksum_valid {
+                warn!(
+                    "SetChecksum mismatch for '{}' (inode 0x{:016x}): stored 0x{:04x}",
+                    fr.name, ino, fr.set_checksum
+                );
+            }
+            if self.upcase_table().name_hash_units(&fr.name_units) != fr.name_hash {
+                warn!(
+                    "NameHash mismatch for '{}' (inode 0x{:016x}): stored 0x{:04x}",
+                    fr.name, ino, fr.name_hash
+                );
+            }
+            let parent = (ino >> 32) as u32;
+            let idx = (ino & 0xFFFF_FFFF) as usize;
+            if fr.is_dir() {
+                self.dir_inodes.insert(fr.first_cluster, ino);
             }
+            self.inode_to_record.insert(ino, (parent, idx, fr));
         }
         self.index_built = true;
         debug!(
@@ -188,6 +650,80 @@ impl<T: Read + Seek> ExFatFS<T> {
         Ok(())
     }
 
+    /// Walk the directory tree from the root, calling `visit` for every entry set
+    /// (see `crate::walk::walk`).
+    pub fn walk(
+        &mut self,
+        opts: &WalkOptions,
+        visit: impl FnMut(WalkEntry) -> Result<(), FsError>,
+    ) -> Result<WalkStats, FsError> {
+        crate::walk::walk(self, opts, visit)
+    }
+
+    /// Snapshot of the fake-inode index: (inode, parent_dir_first_cluster, FileRecord), sorted by inode.
+    /// Recovered lost chains are left out: they are not live allocations.
+    pub(crate) fn indexed_records(&mut self) -> Result<Vec<(u64, u32, FileRecord)>, FsError> {
+        self.ensure_index()?;
+        let mut out: Vec<(u64, u32, FileRecord)> = self
+            .inode_to_record
+            .iter()
+            .filter(|(_, (parent, idx, _))| !is_synthetic(*parent, *idx))
+            .map(|(ino, (parent, _idx, fr))| (*ino, *parent, fr.clone()))
+            .collect();
+        out.sort_by_key(|(ino, _, _)| *ino);
+        Ok(out)
+    }
+
+    /// Find FAT chains no live directory entry references and add them to the fake-inode
+    /// index as files of the virtual `/$LostChains` directory, one per head cluster.
+    /// They are then readable through `read_file`/`read_inode` like any other file.
+    /// Calling it again replaces the previous results.
+    pub fn recover_lost_chains(&mut self) -> Result<Vec<(u64, FileRecord)>, FsError> {
+        let chains = crate::fatcheck::lost_chains(self)?;
+        self.inode_to_record
+            .retain(|_, (parent, idx, _)| !is_synthetic(*parent, *idx));
+        let root = self.bpb.root_dir_first_cluster;
+        let bpc = self.bpb.bytes_per_cluster();
+
+        let dir_ino = ((root as u64) << 32) | 0xFFFF_FFFF;
+        let dir = FileRecord {
+            name: LOST_CHAINS_DIR.to_string(),
+            name_units: LOST_CHAINS_DIR.encode_utf16().collect(),
+            attributes: FileAttributes::DIRECTORY,
+            first_cluster: LOST_CHAINS_CLUSTER,
+            name_length: LOST_CHAINS_DIR.len() as u8,
+            name_hash: self.upcase_table().name_hash(LOST_CHAINS_DIR),
+            ..Default::default()
+        };
+        self.inode_to_record
+            .insert(dir_ino, (root, usize::MAX, dir));
+        self.dir_inodes.insert(LOST_CHAINS_CLUSTER, dir_ino);
+
+        let mut out = Vec::new();
+        for ch in chains {
+            let ino = ((LOST_CHAINS_CLUSTER as u64) << 32) | ch.head as u64;
+            let name = format!("chain_0x{:08x}.chk", ch.head);
+            let size = ch.clusters.len() as u64 * bpc;
+            let fr = FileRecord {
+                name_length: name.len() as u8,
+                name_hash: self.upcase_table().name_hash(&name),
+                name_units: name.encode_utf16().collect(),
+                name,
+                attributes: FileAttributes::ARCHIVE,
+                first_cluster: ch.head,
+                size,
+                general_flags: 0x01, // FAT chained
+                valid_data_length: size,
+                ..Default::default()
+            };
+            self.inode_to_record
+                .insert(ino, (LOST_CHAINS_CLUSTER, ch.head as usize, fr.clone()));
+            out.push((ino, fr));
+        }
+        debug!("recover_lost_chains: {} chains", out.len());
+        Ok(out)
+    }
+
     // exhume_exfat/src/fs.rs
 
     pub fn read_file(&mut self, fr: &FileRecord) -> Result<Vec<u8>, FsError> {
@@ -215,12 +751,13 @@ impl<T: Read + Seek> ExFatFS<T> {
                 remaining -= take;
                 cur += 1; // contiguous
             }
+            zero_past_valid_data(&mut out, fr);
             return Ok(out);
         }
 
         // FAT-chained allocation
         let clusters_needed = (fr.size).div_ceil(self.bpb.bytes_per_cluster()) as usize;
-        let mut fat = Fat::new(&self.bpb, &mut self.io);
+        let mut fat = self.fat();
         let chain = fat.walk_chain(fr.first_cluster, clusters_needed + 4)?;
         if chain.is_empty() && fr.size > 0 {
             warn!(
@@ -238,25 +775,23 @@ impl<T: Read + Seek> ExFatFS<T> {
             }
         }
         out.truncate(fr.size as usize);
+        zero_past_valid_data(&mut out, fr);
         Ok(out)
     }
 
     pub fn read_path(&mut self, path: &str) -> Result<Vec<u8>, FsError> {
         let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
-        let mut cur_dir = self.bpb.root_dir_first_cluster;
+        let mut cur_dir = self.root_dir_record();
         if parts.is_empty() {
             return Err(FsError::NotAFile("/".into()));
         }
 
         for (idx, comp) in parts.iter().enumerate() {
-            let entries = self.list_dir(cur_dir)?;
-            let mut next: Option<FileRecord> = None;
-            for e in entries {
-                if e.name.eq_ignore_ascii_case(comp) {
-                    next = Some(e);
-                    break;
-                }
-            }
+            let entries = self.list_dir_record(&cur_dir)?;
+            let upcase = self.upcase_table();
+            let next = entries
+                .into_iter()
+                .find(|e| upcase.names_equal(&e.name, comp));
             if let Some(fr) = next {
                 if idx == parts.len() - 1 {
                     if fr.is_dir() {
@@ -264,7 +799,7 @@ impl<T: Read + Seek> ExFatFS<T> {
                     }
                     return self.read_file(&fr);
                 } else {
-                    cur_dir = fr.first_cluster;
+                    cur_dir = fr;
                 }
             } else {
                 return Err(FsError::NotFound(format!(
@@ -278,7 +813,17 @@ impl<T: Read + Seek> ExFatFS<T> {
     }
 
     pub fn super_info_json(&self) -> Value {
-        json!({ "bpb": self.bpb.to_json() })
+        json!({
+            "bpb": self.bpb.to_json(),
+            "boot_checksum": self.boot_checksum.to_json(),
+            "extended_boot_sectors": self.extended_boot_sectors(),
+            "oem_parameters": self.oem_parameters().to_json(),
+            "opened_from_backup": self.opened_from_backup,
+            "warnings": self.warnings,
+            "backup_bpb": self.backup_bpb.as_ref().map(|b| b.to_json()),
+            "backup_checksum": self.backup_checksum.as_ref().map(|c| c.to_json()),
+            "boot_diff": self.boot_diff().map(|d| d.to_json()),
+        })
     }
 
     pub fn get_inode(&mut self, inode_num: u64) -> Result<ExInode, FsError> {
@@ -286,8 +831,144 @@ impl<T: Read + Seek> ExFatFS<T> {
         let (_p, _idx, fr) = self
             .inode_to_record
             .get(&inode_num)
+            .cloned()
             .ok_or_else(|| FsError::NotFound(format!("inode 0x{:016x}", inode_num)))?;
-        Ok(ExInode::from_record(inode_num, fr))
+        Ok(self.inode_from_record(inode_num, &fr))
+    }
+
+    /// The raw entries of an inode's entry set: the File entry and its secondaries,
+    /// as found in the parent directory. Works for deleted sets too.
+    pub fn entry_set(&mut self, inode_num: u64) -> Result<Vec<RawDirEnt>, FsError> {
+        self.ensure_index()?;
+        // inodes outside the index (deleted or carved sets) are located from the
+        // inode number itself: parent directory first cluster and entry index
+        let (parent, idx) = match self.inode_to_record.get(&inode_num) {
+            Some((parent, idx, _)) => (*parent, *idx),
+            None => ((inode_num >> 32) as u32, (inode_num & 0xFFFF_FFFF) as usize),
+        };
+        if is_synthetic(parent, idx) {
+            return Err(FsError::NotFound(format!(
+                "entry set of virtual inode 0x{:016x}",
+                inode_num
+            )));
+        }
+        let dir = self.dir_record(parent)?;
+        let ents = self.read_dir_entries(&dir)?;
+        let first = ents
+            .get(idx)
+            .filter(|e| e.kind_normalized() == EntryType::File)
+            .ok_or_else(|| {
+                FsError::NotFound(format!(
+                    "File entry {} of directory cluster {} (inode 0x{:016x})",
+                    idx, parent, inode_num
+                ))
+            })?;
+        let end = (idx + 1 + first.raw[1] as usize).min(ents.len());
+        Ok(ents[idx..end].to_vec())
+    }
+
+    /// Record of the directory starting at `first_cluster`: the root or an indexed
+    /// directory. Other clusters (e.g. deleted directories) have no record to honour;
+    /// they are read as a plain FAT chain to its end, with a warning.
+    fn dir_record(&mut self, first_cluster: u32) -> Result<FileRecord, FsError> {
+        if first_cluster == self.bpb.root_dir_first_cluster {
+            return Ok(self.root_dir_record());
+        }
+        self.ensure_index()?;
+        if let Some((_, _, fr)) = self
+            .dir_inodes
+            .get(&first_cluster)
+            .and_then(|ino| self.inode_to_record.get(ino))
+        {
+            return Ok(fr.clone());
+        }
+        warn!(
+            "no directory record starts at cluster {}; reading it as a FAT chain",
+            first_cluster
+        );
+        Ok(FileRecord {
+            name: format!("cluster_{}", first_cluster),
+            attributes: FileAttributes::DIRECTORY,
+            first_cluster,
+            general_flags: 0x01, // FAT chained, no DataLength
+            ..Default::default()
+        })
+    }
+
+    /// Path of the directory starting at `cluster` ("" for the root), walking up the parents.
+    fn dir_path(&self, mut cluster: u32) -> String {
+        let root = self.bpb.root_dir_first_cluster;
+        let mut parts = Vec::new();
+        // bounded: a corrupted tree may loop
+        while cluster != root && parts.len() < 256 {
+            let Some((parent, _, fr)) = self
+                .dir_inodes
+                .get(&cluster)
+                .and_then(|ino| self.inode_to_record.get(ino))
+            else {
+                parts.push(format!("$Orphan_0x{:08x}", cluster));
+                break;
+            };
+            parts.push(fr.name.clone());
+            cluster = *parent;
+        }
+        parts.reverse();
+        parts.iter().map(|p| format!("/{}", p)).collect()
+    }
+
+    /// Full path ("/dir/name") of `inode_num`. Inodes outside the index (deleted entry
+    /// sets, carving results) are resolved from the inode number itself: the parent
+    /// directory's first cluster and the entry index in it.
+    pub fn path_of(&mut self, inode_num: u64) -> Result<String, FsError> {
+        self.ensure_index()?;
+        let (parent, name) = match self.inode_to_record.get(&inode_num) {
+            Some((parent, _, fr)) => (*parent, fr.name.clone()),
+            None => {
+                let set = self.entry_set(inode_num)?;
+                let fr = assemble_file(&set).ok_or_else(|| {
+                    FsError::Parse(format!(
+                        "undecodable entry set of inode 0x{:016x}",
+                        inode_num
+                    ))
+                })?;
+                ((inode_num >> 32) as u32, fr.name)
+            }
+        };
+        Ok(format!("{}/{}", self.dir_path(parent), name))
+    }
+
+    /// Full paths of every indexed inode.
+    pub(crate) fn indexed_paths(&mut self) -> Result<HashMap<u64, String>, FsError> {
+        self.ensure_index()?;
+        Ok(self
+            .inode_to_record
+            .iter()
+            .map(|(ino, (parent, _, fr))| (*ino, format!("{}/{}", self.dir_path(*parent), fr.name)))
+            .collect())
+    }
+
+    /// `ExInode::from_record` plus the NameHash check against the up-case table and the
+    /// subdirectory links of a directory.
+    fn inode_from_record(&mut self, inode_num: u64, fr: &FileRecord) -> ExInode {
+        let mut inode = ExInode::from_record(inode_num, fr);
+        inode.synthetic = self
+            .inode_to_record
+            .get(&inode_num)
+            .is_some_and(|(parent, idx, _)| is_synthetic(*parent, *idx));
+        if let Ok(path) = self.path_of(inode_num) {
+            inode.path = path;
+        }
+        inode.name_hash_valid =
+            Some(self.upcase_table().name_hash_units(&fr.name_units) == fr.name_hash);
+        if fr.is_dir() {
+            let subdirs = self
+                .inode_to_record
+                .values()
+                .filter(|(parent, _, r)| *parent == fr.first_cluster && r.is_dir())
+                .count();
+            inode.i_links_count = inode.i_links_count.saturating_add(subdirs as u16);
+        }
+        inode
     }
 
     pub fn resolve_path_to_inode_num(&mut self, path: &str) -> Result<(u64, ExInode), FsError> {
@@ -301,9 +982,11 @@ impl<T: Read + Seek> ExFatFS<T> {
         let mut current_inode: Option<u64> = None;
 
         for (pos, comp) in parts.iter().enumerate() {
+            self.upcase_table();
+            let upcase = self.upcase.as_ref().unwrap();
             let mut found: Option<(u64, FileRecord)> = None;
             for (ino, (parent, _idx, fr)) in self.inode_to_record.iter() {
-                if *parent == cur_dir && fr.name.eq_ignore_ascii_case(comp) {
+                if *parent == cur_dir && upcase.names_equal(&fr.name, comp) {
                     found = Some((*ino, fr.clone()));
                     break;
                 }
@@ -328,10 +1011,12 @@ impl<T: Read + Seek> ExFatFS<T> {
         if !inode.is_dir() {
             return Err(FsError::NotFound("not a directory".into()));
         }
+        let dir_path = self.dir_path(inode.first_cluster);
         let mut out = Vec::new();
         for (ino, (parent, _idx, fr)) in self.inode_to_record.iter() {
             if *parent == inode.first_cluster {
-                out.push(CompatDirEntry::from_name_inode(&fr.name, *ino, fr.is_dir()));
+                let path = format!("{}/{}", dir_path, fr.name);
+                out.push(CompatDirEntry::from_record(fr, *ino, &path));
             }
         }
         out.sort_by(|a, b| a.name.cmp(&b.name));
@@ -347,7 +1032,7 @@ impl<T: Read + Seek> ExFatFS<T> {
             .map(|(_, _, fr)| fr.clone())
             .ok_or_else(|| FsError::NotFound(format!("inode 0x{:016x}", inode.i_num)))?;
 
-        if (fr.attributes & 0x0010) != 0 {
+        if fr.is_dir() {
             return Err(FsError::NotAFile(fr.name));
         }
 
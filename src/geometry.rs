@@ -0,0 +1,332 @@
+use crate::bpb::BootSector;
+use crate::direntry::{AllocationBitmapEntry, EntryType, RawDirEnt, UpcaseTableEntry};
+use crate::upcase::upcase_checksum;
+use log::{debug, info};
+use std::io::{Read, Seek, SeekFrom};
+
+/// First 8 bytes of a FAT: media descriptor 0xF8 entry followed by FAT[1] = 0xFFFFFFFF.
+const FAT_SIGNATURE: [u8; 8] = [0xF8, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+/// How far past the FAT we look for the root directory (sectors).
+const ROOT_SCAN_SECTORS: u64 = 1 << 21;
+
+/// Return the first sector in `[from, to)` whose content satisfies `pred`.
+fn scan_sectors<T: Read + Seek>(
+    io: &mut T,
+    bps: u64,
+    from: u64,
+    to: u64,
+    pred: impl Fn(&[u8]) -> bool,
+) -> Option<u64> {
+    const CHUNK_SECTORS: u64 = 2048;
+    let mut sec = from;
+    while sec < to {
+        let n = CHUNK_SECTORS.min(to - sec);
+        let mut buf = vec![0u8; (n * bps) as usize];
+        if io.seek(SeekFrom::Start(sec * bps)).is_err() {
+            return None;
+        }
+        // a short read at the end of the image still gets scanned
+        let mut got = 0;
+        while got < buf.len() {
+            match io.read(&mut buf[got..]) {
+                Ok(0) | Err(_) => break,
+                Ok(k) => got += k,
+            }
+        }
+        for (i, s) in buf[..got].chunks_exact(bps as usize).enumerate() {
+            if pred(s) {
+                return Some(sec + i as u64);
+            }
+        }
+        if got < buf.len() {
+            return None;
+        }
+        sec += n;
+    }
+    None
+}
+
+/// A root directory starts with the volume label, allocation bitmap and up-case table
+/// entries; both 0x81 and 0x82 must appear among the first entries.
+pub(crate) fn looks_like_root_dir(sector: &[u8]) -> bool {
+    let types: Vec<u8> = sector.chunks_exact(32).take(8).map(|e| e[0]).collect();
+    types.contains(&0x81) && types.contains(&0x82)
+}
+
+/// Fill in geometry a damaged boot sector no longer provides, using the other on-disk
+/// structures: the image length, the FAT signature, the heap/cluster-count ratio, a
+/// scan for the root directory and the up-case table and allocation bitmap it points
+/// to. Every value changed is recorded in `warnings`.
+pub(crate) fn infer_geometry<T: Read + Seek>(
+    io: &mut T,
+    bpb: &mut BootSector,
+    warnings: &mut Vec<String>,
+) {
+    let mut note = |msg: String| {
+        info!("{}", msg);
+        warnings.push(msg);
+    };
+    let bps = bpb.bytes_per_sector();
+
+    if bpb.volume_length == 0
+        && let Ok(end) = io.seek(SeekFrom::End(0))
+    {
+        bpb.volume_length = end / bps;
+        note(format!(
+            "inferred volume_length={} from the image size",
+            bpb.volume_length
+        ));
+    }
+
+    if bpb.fat_offset == 0 || bpb.fat_offset as u64 >= bpb.volume_length {
+        let to = bpb.volume_length.min(1 << 16);
+        if let Some(sec) = scan_sectors(io, bps, 1, to, |s| s.starts_with(&FAT_SIGNATURE)) {
+            bpb.fat_offset = sec as u32;
+            note(format!(
+                "inferred fat_offset={} from the FAT signature",
+                sec
+            ));
+        }
+    }
+
+    let heap = bpb.cluster_heap_offset as u64;
+    if bpb.cluster_count < 2 && heap > 0 && bpb.volume_length > heap {
+        bpb.cluster_count = ((bpb.volume_length - heap) >> bpb.sectors_per_cluster_shift) as u32;
+        note(format!(
+            "inferred cluster_count={} from volume_length and cluster_heap_offset",
+            bpb.cluster_count
+        ));
+    }
+
+    if bpb.fat_length == 0 && bpb.cluster_count >= 2 {
+        bpb.fat_length = ((bpb.cluster_count as u64 + 2) * 4).div_ceil(bps) as u32;
+        note(format!(
+            "inferred fat_length={} from cluster_count",
+            bpb.fat_length
+        ));
+    }
+
+    // the heap must hold cluster_count clusters; a shift of 0 is also what the lenient
+    // parser leaves behind for an out-of-range value, so it is re-derived when the heap
+    // clearly has room for larger clusters
+    if heap > 0 && bpb.cluster_count >= 2 && bpb.volume_length > heap {
+        let per_cluster = (bpb.volume_length - heap) / bpb.cluster_count as u64;
+        let spc = bpb.sectors_per_cluster();
+        if per_cluster >= 1
+            && (spc > per_cluster || (bpb.sectors_per_cluster_shift == 0 && per_cluster >= 2))
+        {
+            let shift = (63 - per_cluster.leading_zeros()) as u8;
+            if shift <= 25 - bpb.bytes_per_sector_shift {
+                bpb.sectors_per_cluster_shift = shift;
+                note(format!(
+                    "inferred sectors_per_cluster_shift={} from the heap size",
+                    shift
+                ));
+            }
+        }
+    }
+
+    let root_ok = |io: &mut T, bpb: &BootSector| -> bool {
+        if bpb.cluster_heap_offset == 0
+            || bpb.root_dir_first_cluster < 2
+            || bpb.root_dir_first_cluster as u64 > bpb.cluster_count as u64 + 1
+        {
+            return false;
+        }
+        let mut b = vec![0u8; bps as usize];
+        io.seek(SeekFrom::Start(
+            bpb.cluster_to_byte_offset(bpb.root_dir_first_cluster),
+        ))
+        .and_then(|_| io.read_exact(&mut b))
+        .is_ok()
+            && looks_like_root_dir(&b)
+    };
+    if root_ok(io, bpb) {
+        return;
+    }
+
+    let from = bpb.fat_offset as u64 + bpb.fat_length as u64 * bpb.num_fats as u64;
+    let to = bpb.volume_length.min(from + ROOT_SCAN_SECTORS);
+    let Some(root_sec) = scan_sectors(io, bps, from.max(1), to, looks_like_root_dir) else {
+        debug!("infer_geometry: no root directory found");
+        return;
+    };
+    let spc = bpb.sectors_per_cluster();
+    let heap = bpb.cluster_heap_offset as u64;
+    if heap > 0 && heap <= root_sec && (root_sec - heap).is_multiple_of(spc) {
+        let root = ((root_sec - heap) / spc + 2) as u32;
+        bpb.root_dir_first_cluster = root;
+        note(format!(
+            "inferred root_dir_first_cluster={} from the root directory at sector {}",
+            root, root_sec
+        ));
+    } else if bpb.root_dir_first_cluster >= 2
+        && (bpb.root_dir_first_cluster as u64 - 2) * spc <= root_sec
+    {
+        let heap = root_sec - (bpb.root_dir_first_cluster as u64 - 2) * spc;
+        bpb.cluster_heap_offset = heap as u32;
+        note(format!(
+            "inferred cluster_heap_offset={} from the root directory at sector {}",
+            heap, root_sec
+        ));
+    } else if let Some(g) = locate_heap(io, bpb, root_sec) {
+        bpb.sectors_per_cluster_shift = g.spc_shift;
+        bpb.cluster_heap_offset = g.heap as u32;
+        bpb.root_dir_first_cluster = g.root_cluster;
+        note(format!(
+            "inferred cluster_heap_offset={}, sectors_per_cluster_shift={} and root_dir_first_cluster={} from the up-case table at sector {} and the root directory at sector {}",
+            g.heap, g.spc_shift, g.root_cluster, g.upcase_sec, root_sec
+        ));
+        let by_volume = (bpb.volume_length - g.heap) >> g.spc_shift;
+        let count = (g.bitmap_length as u64 * 8)
+            .min(by_volume)
+            .min(u32::MAX as u64) as u32;
+        if bpb.cluster_count < 2 || bpb.cluster_count as u64 > by_volume {
+            bpb.cluster_count = count;
+            note(format!(
+                "inferred cluster_count={} from the allocation bitmap length and the volume size",
+                count
+            ));
+        }
+        if bpb.fat_length == 0 {
+            bpb.fat_length = ((bpb.cluster_count as u64 + 2) * 4).div_ceil(bps) as u32;
+            note(format!(
+                "inferred fat_length={} from cluster_count",
+                bpb.fat_length
+            ));
+        }
+    } else {
+        note(format!(
+            "root directory found at sector {} but heap offset and root cluster are both unusable",
+            root_sec
+        ));
+    }
+}
+
+/// Largest span between the FAT and the root directory searched for the up-case table.
+const UPCASE_SCAN_SECTORS: u64 = 1 << 17;
+
+/// Cluster heap layout recovered by `locate_heap`.
+struct HeapLayout {
+    heap: u64,
+    spc_shift: u8,
+    root_cluster: u32,
+    upcase_sec: u64,
+    bitmap_length: u32,
+}
+
+fn read_sectors<T: Read + Seek>(io: &mut T, bps: u64, sec: u64, n: u64) -> Option<Vec<u8>> {
+    let mut b = vec![0u8; (n * bps) as usize];
+    io.seek(SeekFrom::Start(sec * bps)).ok()?;
+    io.read_exact(&mut b).ok()?;
+    Some(b)
+}
+
+/// Recover the heap offset and cluster size when neither the heap offset nor the root
+/// cluster survived, from the root directory at `root_sec` alone. Its up-case table
+/// entry gives the table's cluster and checksum: every sector between the FAT and the
+/// root holding a table with that checksum, combined with each cluster size, places the
+/// heap. A placement is kept if the FAT has entries for the bitmap, up-case table and root
+/// clusters and the bitmap marks all three allocated.
+fn locate_heap<T: Read + Seek>(io: &mut T, bpb: &BootSector, root_sec: u64) -> Option<HeapLayout> {
+    let bps = bpb.bytes_per_sector();
+    let root = read_sectors(io, bps, root_sec, 1)?;
+    let ents: Vec<RawDirEnt> = root.chunks_exact(32).map(RawDirEnt::from_bytes).collect();
+    let bitmap = ents
+        .iter()
+        .filter(|e| e.kind() == EntryType::AllocationBitmap)
+        .map(AllocationBitmapEntry::parse)
+        .find(|b| b.bitmap_flags & 0x01 == 0)?;
+    let upcase = ents
+        .iter()
+        .find(|e| e.kind() == EntryType::UpCaseTable)
+        .map(UpcaseTableEntry::parse)?;
+    if bitmap.first_cluster < 2 || upcase.first_cluster < 2 || upcase.data_length == 0 {
+        return None;
+    }
+    let up_sectors = (upcase.data_length as u64).div_ceil(bps);
+    let from = (bpb.fat_offset as u64 + 1).max(root_sec.saturating_sub(UPCASE_SCAN_SECTORS));
+    if from >= root_sec {
+        return None;
+    }
+    let region = read_sectors(io, bps, from, root_sec - from + up_sectors)?;
+
+    let fat_entry = |io: &mut T, c: u32| -> Option<u32> {
+        let mut b = [0u8; 4];
+        io.seek(SeekFrom::Start(bpb.fat_offset as u64 * bps + c as u64 * 4))
+            .ok()?;
+        io.read_exact(&mut b).ok()?;
+        Some(u32::from_le_bytes(b))
+    };
+    let bit_set = |io: &mut T, bitmap_sec: u64, c: u32| -> bool {
+        let mut b = [0u8; 1];
+        io.seek(SeekFrom::Start(bitmap_sec * bps + (c as u64 - 2) / 8))
+            .and_then(|_| io.read_exact(&mut b))
+            .is_ok()
+            && b[0] & (1 << ((c - 2) % 8)) != 0
+    };
+
+    for sec in from..root_sec {
+        let off = ((sec - from) * bps) as usize;
+        let table = &region[off..off + upcase.data_length as usize];
+        // any up-case table maps the first code units to themselves
+        if table[..8] != [0, 0, 1, 0, 2, 0, 3, 0] || upcase_checksum(table) != upcase.table_checksum
+        {
+            continue;
+        }
+        for shift in (0..=25 - bpb.bytes_per_sector_shift).rev() {
+            let spc = 1u64 << shift;
+            let Some(heap) = sec.checked_sub((upcase.first_cluster as u64 - 2) * spc) else {
+                continue;
+            };
+            if heap <= bpb.fat_offset as u64 || !(root_sec - heap).is_multiple_of(spc) {
+                continue;
+            }
+            let Ok(root_cluster) = u32::try_from((root_sec - heap) / spc + 2) else {
+                continue;
+            };
+            let clusters = [bitmap.first_cluster, upcase.first_cluster, root_cluster];
+            let bitmap_sec = heap + (bitmap.first_cluster as u64 - 2) * spc;
+            if clusters
+                .iter()
+                .all(|&c| fat_entry(io, c).is_some_and(|v| v != 0) && bit_set(io, bitmap_sec, c))
+            {
+                debug!(
+                    "locate_heap: up-case table at sector {}, {} sectors per cluster",
+                    sec, spc
+                );
+                return Some(HeapLayout {
+                    heap,
+                    spc_shift: shift,
+                    root_cluster,
+                    upcase_sec: sec,
+                    bitmap_length: bitmap.data_length,
+                });
+            }
+        }
+    }
+    None
+}
+
+/// Geometry the volume cannot be read without, checked after inference.
+pub(crate) fn check_geometry(bpb: &BootSector) -> Result<(), String> {
+    let problem =
+        if bpb.cluster_heap_offset == 0 || bpb.cluster_heap_offset as u64 >= bpb.volume_length {
+            "no cluster heap offset"
+        } else if bpb.cluster_count < 2 {
+            "no cluster count"
+        } else if bpb.root_dir_first_cluster < 2
+            || bpb.root_dir_first_cluster as u64 > bpb.cluster_count as u64 + 1
+        {
+            "no root directory cluster"
+        } else if bpb.fat_offset == 0 || bpb.fat_length == 0 {
+            "no FAT location"
+        } else {
+            return Ok(());
+        };
+    Err(format!(
+        "geometry unusable after inference: {} (heap offset {}, cluster count {}, root cluster {})",
+        problem, bpb.cluster_heap_offset, bpb.cluster_count, bpb.root_dir_first_cluster
+    ))
+}
@@ -0,0 +1,140 @@
+use crate::bpb::format_serial;
+use crate::fs::{ExFatFS, FsError};
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{Read, Seek};
+
+/// Identifiers linking a volume to host artifacts (registry, event logs, LNK files),
+/// plus a guess of which implementation formatted it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VolumeIdentity {
+    pub label: Option<String>,
+    pub volume_guid: Option<String>,
+    pub volume_serial: u32,
+    pub serial_number: String,      // XXXX-XXXX, as Windows shows it
+    pub percent_in_use: Option<u8>, // None: 0xFF (not available)
+    pub formatter_guess: String,
+    pub formatter_evidence: Vec<String>,
+}
+
+impl VolumeIdentity {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+impl std::fmt::Display for VolumeIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut t = Table::new();
+        for (k, v) in [
+            (
+                "Label",
+                self.label.clone().unwrap_or_else(|| "(none)".into()),
+            ),
+            (
+                "Volume GUID",
+                self.volume_guid.clone().unwrap_or_else(|| "(none)".into()),
+            ),
+            (
+                "Serial number",
+                format!("{} (0x{:08X})", self.serial_number, self.volume_serial),
+            ),
+            (
+                "Percent in use",
+                self.percent_in_use
+                    .map(|p| format!("{}%", p))
+                    .unwrap_or_else(|| "not available".into()),
+            ),
+            ("Formatter (guess)", self.formatter_guess.clone()),
+        ] {
+            t.add_row(Row::new(vec![Cell::new(k), Cell::new(&v)]));
+        }
+        for e in &self.formatter_evidence {
+            t.add_row(Row::new(vec![Cell::new("Evidence"), Cell::new(e)]));
+        }
+        write!(f, "{}", t)
+    }
+}
+
+/// Guess the formatting implementation from the boot code, the OEM flash parameters
+/// and the on-disk layout. Returns the guess and the observations behind it.
+fn guess_formatter<T: Read + Seek>(fs: &ExFatFS<T>) -> (String, Vec<String>) {
+    let mut evidence = Vec::new();
+    let code = fs.boot_code_report();
+    let flash = fs.oem_parameters().flash().cloned();
+    let bpb = &fs.bpb;
+
+    match &code.known_match {
+        Some(name) if code.known_verified => evidence.push(format!("boot code: {}", name)),
+        Some(name) => evidence.push(format!("boot code: {} (unverified)", name)),
+        None if code.nonzero_bytes > 0 => {
+            evidence.push(format!("boot code: unknown (SHA-256 {})", code.sha256))
+        }
+        None => {}
+    }
+    if let Some(fp) = &flash {
+        evidence.push(format!(
+            "flash parameters present (erase block {} bytes, page {} bytes)",
+            fp.erase_block_size, fp.page_size
+        ));
+    }
+
+    // SD-specification formatters align the cluster heap to the erase block / boundary unit
+    let bps = bpb.bytes_per_sector();
+    let heap_bytes = bpb.cluster_heap_offset as u64 * bps;
+    let align = flash
+        .as_ref()
+        .map(|fp| fp.erase_block_size as u64)
+        .filter(|&e| e > 0)
+        .unwrap_or(4 << 20);
+    let heap_aligned = heap_bytes > 0 && heap_bytes.is_multiple_of(align);
+    if heap_aligned {
+        evidence.push(format!(
+            "cluster heap at {} bytes, aligned to {} bytes",
+            heap_bytes, align
+        ));
+    }
+    if bpb.fat_offset == 24 {
+        evidence.push("FAT directly after the boot regions (sector 24, no alignment)".into());
+    }
+    if bpb.num_fats == 2 {
+        evidence.push("two FATs (TexFAT)".into());
+    }
+
+    // only exact (hash or fill) matches name a formatter outright
+    let known = code.known_match.as_deref().unwrap_or("");
+    let guess = if known.starts_with("Windows") && code.known_verified {
+        "Windows"
+    } else if known.starts_with("Windows") {
+        "possibly Windows (unverified: boot code strings only)"
+    } else if known.starts_with("mkfs.exfat") {
+        "mkfs.exfat (Linux)"
+    } else if bpb.num_fats == 2 {
+        "TexFAT-capable device (Windows CE / embedded)"
+    } else if known.starts_with("Zero-filled") && (flash.is_some() || heap_aligned) {
+        "SD Card Formatter or device firmware (SD specification layout)"
+    } else if known.starts_with("Zero-filled") {
+        "embedded formatter (camera, phone or other device)"
+    } else {
+        "unknown"
+    };
+    (guess.to_string(), evidence)
+}
+
+/// Build the volume identity report.
+pub fn volume_identity<T: Read + Seek>(fs: &mut ExFatFS<T>) -> Result<VolumeIdentity, FsError> {
+    let label = fs.volume_label()?;
+    let volume_guid = fs.volume_guid()?.map(|g| g.guid);
+    let (formatter_guess, formatter_evidence) = guess_formatter(fs);
+    let pct = fs.bpb.percent_in_use;
+    Ok(VolumeIdentity {
+        label,
+        volume_guid,
+        volume_serial: fs.bpb.volume_serial,
+        serial_number: format_serial(fs.bpb.volume_serial),
+        percent_in_use: (pct != 0xFF).then_some(pct),
+        formatter_guess,
+        formatter_evidence,
+    })
+}
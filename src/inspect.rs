@@ -0,0 +1,236 @@
+use crate::bpb::format_guid;
+use crate::direntry::{EntryType, FileAttributes, RawDirEnt};
+use crate::exinode::{format_timestamp, format_utc_offset};
+use crate::fs::{ExFatFS, FsError};
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{Read, Seek};
+
+/// One field of a directory entry: its bytes and their meaning.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntryField {
+    pub offset: usize, // within the 32-byte entry
+    pub length: usize,
+    pub name: String,
+    pub hex: String,
+    pub value: String,
+}
+
+/// A directory entry of a set, located on disk and decoded field by field.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InspectedEntry {
+    pub volume_offset: u64,
+    pub body_offset: u64,
+    pub entry_type: u8,
+    pub in_use: bool,
+    pub type_name: String,
+    pub fields: Vec<EntryField>,
+    pub hex: String,
+}
+
+/// Every entry of an inode's entry set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntrySetReport {
+    pub inode: u64,
+    pub entries: Vec<InspectedEntry>,
+}
+
+impl EntrySetReport {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+fn hex(b: &[u8]) -> String {
+    b.iter()
+        .map(|x| format!("{:02x}", x))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn type_name(kind: EntryType) -> &'static str {
+    match kind {
+        EntryType::AllocationBitmap => "Allocation Bitmap",
+        EntryType::UpCaseTable => "Up-case Table",
+        EntryType::VolumeLabel => "Volume Label",
+        EntryType::File => "File",
+        EntryType::VolumeGuid => "Volume GUID",
+        EntryType::TexFatPadding => "TexFAT Padding",
+        EntryType::StreamExt => "Stream Extension",
+        EntryType::FileName => "File Name",
+        EntryType::VendorExtension => "Vendor Extension",
+        EntryType::VendorAllocation => "Vendor Allocation",
+        EntryType::Unknown(_) => "Unknown",
+        EntryType::End => "End of directory",
+    }
+}
+
+/// Decode the fields of a raw entry according to its type (InUse bit ignored).
+pub fn decode_fields(e: &RawDirEnt) -> Vec<EntryField> {
+    let b = &e.raw;
+    let le_u16 = |o: usize| u16::from_le_bytes([b[o], b[o + 1]]);
+    let le_u32 = |o: usize| u32::from_le_bytes(b[o..o + 4].try_into().unwrap());
+    let le_u64 = |o: usize| u64::from_le_bytes(b[o..o + 8].try_into().unwrap());
+    let mut out = Vec::new();
+    let mut f = |offset: usize, length: usize, name: &str, value: String| {
+        out.push(EntryField {
+            offset,
+            length,
+            name: name.to_string(),
+            hex: hex(&b[offset..offset + length]),
+            value,
+        });
+    };
+
+    f(
+        0,
+        1,
+        "EntryType",
+        format!(
+            "0x{:02x} ({}, InUse={})",
+            b[0],
+            type_name(e.kind_normalized()),
+            e.is_active()
+        ),
+    );
+    match e.kind_normalized() {
+        EntryType::File => {
+            f(1, 1, "SecondaryCount", b[1].to_string());
+            f(2, 2, "SetChecksum", format!("0x{:04x}", le_u16(2)));
+            f(
+                4,
+                2,
+                "FileAttributes",
+                FileAttributes::from_raw(le_u16(4)).to_string(),
+            );
+            f(6, 2, "Reserved1", String::new());
+            for (o, inc, tz, name) in [
+                (8, Some(20), 22, "CreateTimestamp"),
+                (12, Some(21), 23, "LastModifiedTimestamp"),
+                (16, None, 24, "LastAccessedTimestamp"),
+            ] {
+                let inc = inc.map(|i: usize| b[i]).unwrap_or(0);
+                f(o, 4, name, format_timestamp(le_u32(o), inc, b[tz]));
+            }
+            f(
+                20,
+                1,
+                "Create10msIncrement",
+                format!("{} ms", b[20] as u32 * 10),
+            );
+            f(
+                21,
+                1,
+                "LastModified10msIncrement",
+                format!("{} ms", b[21] as u32 * 10),
+            );
+            f(22, 1, "CreateUtcOffset", format_utc_offset(b[22]));
+            f(23, 1, "LastModifiedUtcOffset", format_utc_offset(b[23]));
+            f(24, 1, "LastAccessedUtcOffset", format_utc_offset(b[24]));
+            f(25, 7, "Reserved2", String::new());
+        }
+        EntryType::StreamExt => {
+            f(
+                1,
+                1,
+                "GeneralSecondaryFlags",
+                format!(
+                    "0x{:02x} (AllocationPossible={}, NoFatChain={})",
+                    b[1],
+                    b[1] & 0x01 != 0,
+                    b[1] & 0x02 != 0
+                ),
+            );
+            f(2, 1, "Reserved1", String::new());
+            f(3, 1, "NameLength", b[3].to_string());
+            f(4, 2, "NameHash", format!("0x{:04x}", le_u16(4)));
+            f(6, 2, "Reserved2", String::new());
+            f(8, 8, "ValidDataLength", le_u64(8).to_string());
+            f(16, 4, "Reserved3", String::new());
+            f(20, 4, "FirstCluster", le_u32(20).to_string());
+            f(24, 8, "DataLength", le_u64(24).to_string());
+        }
+        EntryType::FileName => {
+            f(1, 1, "GeneralSecondaryFlags", format!("0x{:02x}", b[1]));
+            let units: Vec<u16> = b[2..32]
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .take_while(|&u| u != 0)
+                .collect();
+            f(2, 30, "FileName", String::from_utf16_lossy(&units));
+        }
+        EntryType::VendorExtension => {
+            f(1, 1, "GeneralSecondaryFlags", format!("0x{:02x}", b[1]));
+            f(2, 16, "VendorGuid", format_guid(&b[2..18]));
+            f(18, 14, "VendorDefined", String::new());
+        }
+        EntryType::VendorAllocation => {
+            f(1, 1, "GeneralSecondaryFlags", format!("0x{:02x}", b[1]));
+            f(2, 16, "VendorGuid", format_guid(&b[2..18]));
+            f(18, 2, "VendorDefined", format!("0x{:04x}", le_u16(18)));
+            f(20, 4, "FirstCluster", le_u32(20).to_string());
+            f(24, 8, "DataLength", le_u64(24).to_string());
+        }
+        _ => f(1, 31, "Data", String::new()),
+    }
+    out
+}
+
+/// Locate and decode every entry of the set behind `inode`.
+/// `partition_offset` is the volume's byte offset in the body.
+pub fn inspect_entry_set<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    inode: u64,
+    partition_offset: u64,
+) -> Result<EntrySetReport, FsError> {
+    let entries = fs
+        .entry_set(inode)?
+        .iter()
+        .map(|e| InspectedEntry {
+            volume_offset: e.volume_offset,
+            body_offset: partition_offset + e.volume_offset,
+            entry_type: e.entry_type,
+            in_use: e.is_active(),
+            type_name: type_name(e.kind_normalized()).to_string(),
+            fields: decode_fields(e),
+            hex: hex(&e.raw),
+        })
+        .collect();
+    Ok(EntrySetReport { inode, entries })
+}
+
+impl std::fmt::Display for EntrySetReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, e) in self.entries.iter().enumerate() {
+            writeln!(
+                f,
+                "Entry {} of inode 0x{:016x}: {} (0x{:02x}, {}) at volume 0x{:x} / body 0x{:x}",
+                i,
+                self.inode,
+                e.type_name,
+                e.entry_type,
+                if e.in_use { "in use" } else { "not in use" },
+                e.volume_offset,
+                e.body_offset
+            )?;
+            let mut t = Table::new();
+            t.add_row(Row::new(vec![
+                Cell::new("Offset"),
+                Cell::new("Field"),
+                Cell::new("Bytes"),
+                Cell::new("Value"),
+            ]));
+            for fl in &e.fields {
+                t.add_row(Row::new(vec![
+                    Cell::new(&format!("0x{:02x}", fl.offset)),
+                    Cell::new(&fl.name),
+                    Cell::new(&fl.hex),
+                    Cell::new(&fl.value),
+                ]));
+            }
+            writeln!(f, "{}", t)?;
+        }
+        Ok(())
+    }
+}
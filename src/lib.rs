@@ -1,9 +1,20 @@
+pub mod bitmap;
+pub mod bootcode;
 pub mod bpb;
 pub mod carve;
 pub mod compat;
 pub mod direntry;
+pub mod discover;
 pub mod exinode;
 pub mod fat;
+pub mod fatcheck;
 pub mod fs;
+mod geometry;
+pub mod identity;
+pub mod inspect;
+pub mod slack;
+pub mod unalloc;
+pub mod upcase;
+pub mod walk;
 pub use crate::bpb::BootSector;
-pub use crate::fs::ExFatFS;
+pub use crate::fs::{ExFatFS, ExFatOptions};
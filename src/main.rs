@@ -1,11 +1,27 @@
 use clap::*;
 use clap_num::maybe_hex;
 use exhume_body::{Body, BodySlice};
-use exhume_exfat::ExFatFS;
+use exhume_exfat::bootcode::parse_known_boot_code;
+use exhume_exfat::discover::discover_volumes;
+use exhume_exfat::fat::DEFAULT_FAT_CACHE_BYTES;
+use exhume_exfat::fatcheck::analyze_fat;
+use exhume_exfat::identity::volume_identity;
+use exhume_exfat::inspect::inspect_entry_set;
+use exhume_exfat::slack::{FileSlack, file_slack, for_each_slack, slack_table};
+use exhume_exfat::unalloc::export_unallocated;
+use exhume_exfat::walk::{ErrorPolicy, WalkOptions};
+use exhume_exfat::{ExFatFS, ExFatOptions};
 use log::{error, info};
 use serde_json::{Value, json};
-use std::fs::File;
-use std::io::Write;
+use std::fs::{File, create_dir_all};
+use std::io::{BufWriter, Write};
+
+/// Parse a size in MiB into bytes.
+fn parse_mib(s: &str) -> Result<usize, String> {
+    let mib: usize = s.parse().map_err(|e| format!("{}", e))?;
+    mib.checked_mul(1024 * 1024)
+        .ok_or_else(|| format!("{} MiB is too large", mib))
+}
 
 fn main() {
     let matches = Command::new("exhume_exfat")
@@ -33,16 +49,22 @@ fn main() {
                 .short('o')
                 .long("offset")
                 .value_parser(maybe_hex::<u64>)
-                .required(true)
-                .help("The exFAT partition start offset (bytes, dec or hex)."),
+                .required(false)
+                .help("The exFAT partition start offset (bytes, dec or hex). Discovered if omitted."),
         )
         .arg(
             Arg::new("size")
                 .short('s')
                 .long("size")
                 .value_parser(maybe_hex::<u64>)
-                .required(true)
-                .help("The size of the exFAT partition in sectors (dec or hex)."),
+                .required(false)
+                .help("The size of the exFAT partition in sectors (dec or hex). Discovered if omitted."),
+        )
+        .arg(
+            Arg::new("discover")
+                .long("discover")
+                .action(ArgAction::SetTrue)
+                .help("List exFAT volumes found in the body (MBR/GPT, then signature scan)."),
         )
         .arg(
             Arg::new("bpb")
@@ -50,6 +72,125 @@ fn main() {
                 .action(ArgAction::SetTrue)
                 .help("Display boot sector / BPB info."),
         )
+        .arg(
+            Arg::new("boot_diff")
+                .long("boot-diff")
+                .action(ArgAction::SetTrue)
+                .help("Compare the main and backup boot sectors field by field."),
+        )
+        .arg(
+            Arg::new("backup_fallback")
+                .long("backup-fallback")
+                .action(ArgAction::SetTrue)
+                .help("Open from the backup boot region if the main one is invalid."),
+        )
+        .arg(
+            Arg::new("lenient")
+                .long("lenient")
+                .action(ArgAction::SetTrue)
+                .help("Tolerate boot sector violations and infer damaged geometry."),
+        )
+        .arg(
+            Arg::new("boot_code")
+                .long("boot-code")
+                .action(ArgAction::SetTrue)
+                .help("Hash the boot code and compare it with known formatters."),
+        )
+        .arg(
+            Arg::new("boot_code_known")
+                .long("boot-code-known")
+                .value_parser(value_parser!(String))
+                .requires("boot_code")
+                .help("Reference boot code hashes ('<sha256> <name>' per line), e.g. from Windows or macOS formatted media."),
+        )
+        .arg(
+            Arg::new("boot_code_dump")
+                .long("boot-code-dump")
+                .value_parser(value_parser!(String))
+                .help("Write the boot region code (sector 0 boot code, then sectors 1..=8) to this file."),
+        )
+        .arg(
+            Arg::new("fat_cache")
+                .long("fat-cache")
+                .value_parser(parse_mib)
+                .help("FAT cache size in MiB (default 64, 0 disables it)."),
+        )
+        .arg(
+            Arg::new("fat_check")
+                .long("fat-check")
+                .action(ArgAction::SetTrue)
+                .help("Check every FAT entry for bad, cross-linked, looping and orphan chains."),
+        )
+        .arg(
+            Arg::new("lost_chains")
+                .long("lost-chains")
+                .action(ArgAction::SetTrue)
+                .help("Recover unreferenced FAT chains as files of the virtual /$LostChains directory."),
+        )
+        .arg(
+            Arg::new("unalloc")
+                .long("unalloc")
+                .value_parser(value_parser!(String))
+                .help("Write all unallocated clusters to this file, with an offset map in '<file>.map.csv'."),
+        )
+        .arg(
+            Arg::new("slack")
+                .long("slack")
+                .action(ArgAction::SetTrue)
+                .help("List file slack of --inode, or of every allocated file if --inode is not set."),
+        )
+        .arg(
+            Arg::new("slack_out")
+                .long("slack-out")
+                .value_parser(value_parser!(String))
+                .requires("slack")
+                .help("Write each file's slack to '<dir>/slack_<inode>.bin'."),
+        )
+        .arg(
+            Arg::new("upcase")
+                .long("upcase")
+                .action(ArgAction::SetTrue)
+                .help("Verify the up-case table checksum and report a non-standard table."),
+        )
+        .arg(
+            Arg::new("identity")
+                .long("identity")
+                .action(ArgAction::SetTrue)
+                .help("Show the volume label, GUID, serial number and a guess of the formatter."),
+        )
+        .arg(
+            Arg::new("entries")
+                .long("entries")
+                .requires("inode")
+                .action(ArgAction::SetTrue)
+                .help("Show every directory entry of --inode's entry set with its offsets and decoded bytes."),
+        )
+        .arg(
+            Arg::new("walk")
+                .long("walk")
+                .action(ArgAction::SetTrue)
+                .help("List every entry of the directory tree with its full path."),
+        )
+        .arg(
+            Arg::new("max_depth")
+                .long("max-depth")
+                .value_parser(value_parser!(usize))
+                .requires("walk")
+                .help("Deepest level listed by --walk (entries of the root are depth 0)."),
+        )
+        .arg(
+            Arg::new("deleted")
+                .long("deleted")
+                .requires("walk")
+                .action(ArgAction::SetTrue)
+                .help("Include deleted entry sets in --walk."),
+        )
+        .arg(
+            Arg::new("texfat")
+                .long("texfat")
+                .action(ArgAction::SetTrue)
+                .help("Compare the active and inactive FAT/bitmap of a TexFAT volume."),
+        )
         .arg(
             Arg::new("root")
                 .short('R')
@@ -91,7 +232,7 @@ fn main() {
                 .long("dump")
                 .requires("inode")
                 .action(ArgAction::SetTrue)
-                .help("When --inode is set, dump content to 'inode_<N>.bin'"),
+                .help("When --inode is set, dump content to 'inode_<N>.bin' (vendor allocations to 'inode_<N>.vendor<K>.bin')"),
         )
         // .arg(
         //     Arg::new("carve")
@@ -133,10 +274,30 @@ fn main() {
     let file_path = matches.get_one::<String>("body").unwrap();
     let auto = String::from("auto");
     let format = matches.get_one::<String>("format").unwrap_or(&auto);
-    let offset = matches.get_one::<u64>("offset").unwrap();
-    let size = matches.get_one::<u64>("size").unwrap();
+    let offset = matches.get_one::<u64>("offset").copied();
+    let size = matches.get_one::<u64>("size").copied();
+    let discover = matches.get_flag("discover");
 
     let show_bpb = matches.get_flag("bpb");
+    let show_boot_diff = matches.get_flag("boot_diff");
+    let show_texfat = matches.get_flag("texfat");
+    let fat_check = matches.get_flag("fat_check");
+    let lost_chains = matches.get_flag("lost_chains");
+    let unalloc_out = matches.get_one::<String>("unalloc");
+    let show_slack = matches.get_flag("slack");
+    let show_upcase = matches.get_flag("upcase");
+    let show_identity = matches.get_flag("identity");
+    let show_entries = matches.get_flag("entries");
+    let do_walk = matches.get_flag("walk");
+    let walk_opts = WalkOptions {
+        max_depth: matches.get_one::<usize>("max_depth").copied(),
+        include_deleted: matches.get_flag("deleted"),
+        on_error: ErrorPolicy::Continue,
+    };
+    let slack_out = matches.get_one::<String>("slack_out");
+    let show_boot_code = matches.get_flag("boot_code");
+    let boot_code_known = matches.get_one::<String>("boot_code_known");
+    let boot_code_dump = matches.get_one::<String>("boot_code_dump");
     let json_output = matches.get_flag("json");
     let list_root = matches.get_flag("root");
     let inode_num = matches.get_one::<u64>("inode").copied().unwrap_or(0);
@@ -151,8 +312,45 @@ fn main() {
 
     // Body / slice
     let body = Body::new(file_path.to_owned(), format);
-    let partition_size = *size * body.get_sector_size() as u64;
-    let mut slice = match BodySlice::new(&body, *offset, partition_size) {
+    let sector_size = body.get_sector_size() as u64;
+
+    let (offset, partition_size) = match (offset, size) {
+        (Some(o), Some(s)) if !discover => (o, s * sector_size),
+        _ => {
+            let mut probe = body.clone();
+            let image_size = body.get_image_size();
+            let volumes = discover_volumes(&mut probe, sector_size, image_size);
+            if discover {
+                if json_output {
+                    let arr: Vec<Value> = volumes.iter().map(|v| v.to_json()).collect();
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({ "volumes": arr })).unwrap()
+                    );
+                } else {
+                    for v in &volumes {
+                        println!("{}", v);
+                    }
+                }
+                return;
+            }
+            match volumes
+                .iter()
+                .find(|v| offset.is_none_or(|o| o == v.offset))
+            {
+                Some(v) => {
+                    info!("using exFAT volume at 0x{:x} ({} bytes)", v.offset, v.size);
+                    (v.offset, v.size)
+                }
+                None => {
+                    error!("no exFAT volume found; use --offset and --size");
+                    return;
+                }
+            }
+        }
+    };
+
+    let mut slice = match BodySlice::new(&body, offset, partition_size) {
         Ok(sl) => sl,
         Err(e) => {
             error!("Could not create BodySlice: {}", e);
@@ -160,7 +358,15 @@ fn main() {
         }
     };
 
-    let mut fs = match ExFatFS::new(&mut slice) {
+    let opts = ExFatOptions {
+        backup_boot_fallback: matches.get_flag("backup_fallback"),
+        lenient: matches.get_flag("lenient"),
+        fat_cache_bytes: matches
+            .get_one::<usize>("fat_cache")
+            .copied()
+            .unwrap_or(DEFAULT_FAT_CACHE_BYTES),
+    };
+    let mut fs = match ExFatFS::new_with_options(&mut slice, opts) {
         Ok(v) => v,
         Err(e) => {
             error!("Couldn't open exFAT: {}", e);
@@ -175,6 +381,39 @@ fn main() {
     //     }
     // }
 
+    // before --inode, so the recovered chains can be inspected and dumped
+    if lost_chains {
+        match fs.recover_lost_chains() {
+            Ok(list) => {
+                if json_output {
+                    let arr: Vec<Value> = list
+                        .into_iter()
+                        .map(|(inode, r)| {
+                            json!({
+                                "inode": format!("0x{:016x}", inode),
+                                "name": r.name,
+                                "first_cluster": r.first_cluster,
+                                "size": r.size
+                            })
+                        })
+                        .collect();
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({ "lost_chains": arr })).unwrap()
+                    );
+                } else {
+                    for (inode, f) in list {
+                        println!(
+                            "0x{:016x}  {:>10}  cluster {:>8}  /$LostChains/{}",
+                            inode, f.size, f.first_cluster, f.name
+                        );
+                    }
+                }
+            }
+            Err(e) => error!("Lost chain recovery failed: {}", e),
+        }
+    }
+
     if list_root {
         match fs.list_root_with_inodes() {
             Ok(list) => {
@@ -227,7 +466,7 @@ fn main() {
                                     for de in entries {
                                         println!(
                                             "0x{:016x} / 0x{:x} {}",
-                                            de.inode, de.file_type, de.name
+                                            de.inode, de.file_type, de.path
                                         );
                                     }
                                 }
@@ -269,20 +508,287 @@ fn main() {
                             }
                             Err(e) => error!("read_inode failed: {}", e),
                         }
+                        for (k, va) in inode.vendor_allocations.iter().enumerate() {
+                            let filename = format!("inode_0x{:016x}.vendor{}.bin", inode_num, k);
+                            match fs.read_vendor_allocation(va).and_then(|bytes| {
+                                File::create(&filename)?.write_all(&bytes)?;
+                                Ok(bytes.len())
+                            }) {
+                                Ok(n) => info!("wrote {} bytes to '{}'", n, filename),
+                                Err(e) => error!("vendor allocation dump failed: {}", e),
+                            }
+                        }
                     }
                 }
             }
+            // not indexed (e.g. a deleted set): --entries still reads it from disk
+            Err(e) if show_entries => info!("inode 0x{:016x} not indexed: {}", inode_num, e),
             Err(e) => error!("cannot get inode 0x{:016x}: {}", inode_num, e),
         }
     }
     if show_bpb {
+        let usage = match fs.percent_in_use_check() {
+            Ok(u) => Some(u),
+            Err(e) => {
+                error!("PercentInUse check failed: {}", e);
+                None
+            }
+        };
+        if json_output {
+            let mut info = fs.super_info_json();
+            info["percent_in_use_check"] = json!(usage.map(|u| u.to_json()));
+            println!("{}", serde_json::to_string_pretty(&info).unwrap());
+        } else {
+            println!("{}", fs.bpb);
+            for w in &fs.warnings {
+                println!("warning: {}", w);
+            }
+            if let Some(u) = usage {
+                println!("{}", u);
+            }
+            println!("{}", fs.boot_checksum);
+            let bad: Vec<String> = fs
+                .extended_boot_sectors()
+                .iter()
+                .filter(|e| !e.valid_signature)
+                .map(|e| e.sector.to_string())
+                .collect();
+            if !bad.is_empty() {
+                println!(
+                    "Extended boot sectors without 0xAA550000 signature: {}",
+                    bad.join(", ")
+                );
+            }
+            println!("{}", fs.oem_parameters());
+        }
+    }
+    if show_boot_diff {
+        match fs.boot_diff() {
+            Some(diff) => {
+                if json_output {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({
+                            "boot_diff": diff.to_json(),
+                            "main_checksum": fs.boot_checksum.to_json(),
+                            "backup_checksum": fs.backup_checksum.as_ref().map(|c| c.to_json()),
+                        }))
+                        .unwrap()
+                    );
+                } else {
+                    println!("{}", diff);
+                    if let Some(c) = &fs.backup_checksum {
+                        println!("Backup region:\n{}", c);
+                    }
+                }
+            }
+            None if fs.opened_from_backup => {
+                error!("volume opened from the backup boot region; no main boot sector to compare")
+            }
+            None => error!("backup boot sector unavailable"),
+        }
+    }
+    if show_texfat {
+        match fs.texfat_diff() {
+            Ok(diff) => {
+                if json_output {
+                    println!("{}", serde_json::to_string_pretty(&diff.to_json()).unwrap());
+                } else {
+                    println!("{}", diff);
+                }
+            }
+            Err(e) => error!("TexFAT comparison failed: {}", e),
+        }
+    }
+    if show_boot_code {
+        let extra = match boot_code_known.map(std::fs::read_to_string) {
+            Some(Ok(text)) => parse_known_boot_code(&text),
+            Some(Err(e)) => {
+                error!("cannot read boot code references: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        let report = fs.boot_code_report_with(&extra);
         if json_output {
             println!(
                 "{}",
-                serde_json::to_string_pretty(&fs.super_info_json()).unwrap()
+                serde_json::to_string_pretty(&report.to_json()).unwrap()
             );
         } else {
-            println!("{}", fs.bpb);
+            println!("{}", report);
+        }
+    }
+    if let Some(path) = boot_code_dump {
+        let bps = fs.bytes_per_sector() as usize;
+        let mut code = fs.bpb.boot_code.clone();
+        code.extend_from_slice(&fs.boot_region()[bps..9 * bps]);
+        match File::create(path).and_then(|mut f| f.write_all(&code)) {
+            Ok(()) => info!("wrote {} bytes of boot code to '{}'", code.len(), path),
+            Err(e) => error!("write failed for '{}': {}", path, e),
+        }
+    }
+    if fat_check {
+        match analyze_fat(&mut fs) {
+            Ok(report) => {
+                if json_output {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&report.to_json()).unwrap()
+                    );
+                } else {
+                    println!("{}", report);
+                }
+            }
+            Err(e) => error!("FAT analysis failed: {}", e),
+        }
+    }
+    if let Some(path) = unalloc_out {
+        let map_path = format!("{}.map.csv", path);
+        let files = File::create(path).and_then(|d| Ok((d, File::create(&map_path)?)));
+        match files {
+            Ok((d, m)) => {
+                let (mut d, mut m) = (BufWriter::new(d), BufWriter::new(m));
+                match export_unallocated(&mut fs, offset, &mut d, &mut m) {
+                    Ok(summary) => {
+                        if json_output {
+                            println!(
+                                "{}",
+                                serde_json::to_string_pretty(&summary.to_json()).unwrap()
+                            );
+                        } else {
+                            println!(
+                                "{} unallocated clusters ({} bytes, {} runs) -> '{}', map -> '{}'",
+                                summary.clusters, summary.bytes, summary.runs, path, map_path
+                            );
+                        }
+                    }
+                    Err(e) => error!("Unallocated export failed: {}", e),
+                }
+            }
+            Err(e) => error!("create failed for '{}': {}", path, e),
+        }
+    }
+    if show_slack {
+        if let Some(dir) = slack_out
+            && let Err(e) = create_dir_all(dir)
+        {
+            error!("create failed for '{}': {}", dir, e);
+            return;
+        }
+        let mut found: Vec<FileSlack> = Vec::new();
+        let mut keep = |mut s: FileSlack| {
+            if let Some(dir) = slack_out {
+                let p = format!("{}/slack_0x{:016x}.bin", dir, s.inode);
+                File::create(&p).and_then(|mut f| f.write_all(&s.data))?;
+            }
+            s.data = Vec::new();
+            found.push(s);
+            Ok(())
+        };
+        let res = if inode_num > 0 {
+            file_slack(&mut fs, inode_num).and_then(|s| s.map_or(Ok(()), &mut keep))
+        } else {
+            for_each_slack(&mut fs, &mut keep).map(|_| ())
+        };
+        match res {
+            Ok(()) => {
+                if json_output {
+                    let arr: Vec<Value> = found.iter().map(|s| s.to_json()).collect();
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&json!({ "slack": arr })).unwrap()
+                    );
+                } else {
+                    slack_table(&found).printstd();
+                }
+            }
+            Err(e) => error!("Slack extraction failed: {}", e),
+        }
+    }
+    if show_upcase {
+        match fs.read_upcase_table() {
+            Ok(table) => {
+                if json_output {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&table.to_json()).unwrap()
+                    );
+                } else {
+                    println!("{}", table);
+                }
+            }
+            Err(e) => error!("Up-case table read failed: {}", e),
+        }
+    }
+    if show_identity {
+        match volume_identity(&mut fs) {
+            Ok(id) => {
+                if json_output {
+                    println!("{}", serde_json::to_string_pretty(&id.to_json()).unwrap());
+                } else {
+                    println!("{}", id);
+                }
+            }
+            Err(e) => error!("Volume identity failed: {}", e),
+        }
+    }
+    if show_entries {
+        match inspect_entry_set(&mut fs, inode_num, offset) {
+            Ok(report) => {
+                if json_output {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(&report.to_json()).unwrap()
+                    );
+                } else {
+                    print!("{}", report);
+                }
+            }
+            Err(e) => error!("Entry set inspection failed: {}", e),
+        }
+    }
+    if do_walk {
+        let mut arr: Vec<Value> = Vec::new();
+        let res = fs.walk(&walk_opts, |e| {
+            if json_output {
+                arr.push(json!({
+                    "inode": format!("0x{:016x}", e.inode),
+                    "path": e.path,
+                    "depth": e.depth,
+                    "deleted": e.deleted,
+                    "attributes": e.record.attributes,
+                    "first_cluster": e.record.first_cluster,
+                    "size": e.record.size
+                }));
+            } else {
+                println!(
+                    "0x{:016x}  {:>10}  cluster {:>8}  {}{}",
+                    e.inode,
+                    e.record.size,
+                    e.record.first_cluster,
+                    e.path,
+                    if e.deleted { " (deleted)" } else { "" }
+                );
+            }
+            Ok(())
+        });
+        match res {
+            Ok(stats) => {
+                for msg in &stats.errors {
+                    error!("Walk skipped {}", msg);
+                }
+                if json_output {
+                    println!(
+                        "{}",
+                        serde_json::to_string_pretty(
+                            &json!({ "walk": arr, "errors": stats.errors })
+                        )
+                        .unwrap()
+                    );
+                }
+            }
+            Err(e) => error!("Walk failed: {}", e),
         }
     }
 }
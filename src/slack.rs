@@ -0,0 +1,125 @@
+use crate::direntry::FileRecord;
+use crate::fs::{ExFatFS, FsError};
+use log::{debug, warn};
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{Read, Seek};
+
+/// Bytes between the end of a file's data (DataLength) and the end of its last cluster.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileSlack {
+    pub inode: u64,
+    pub path: String,
+    pub cluster: u32,       // last cluster of the file
+    pub volume_offset: u64, // first slack byte, from the start of the volume
+    pub length: u64,
+    pub all_zero: bool,
+    #[serde(skip)]
+    pub data: Vec<u8>,
+}
+
+impl FileSlack {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+/// Table of slack regions (without the data).
+pub fn slack_table(slack: &[FileSlack]) -> Table {
+    let mut t = Table::new();
+    t.add_row(Row::new(vec![
+        Cell::new("Inode"),
+        Cell::new("Path"),
+        Cell::new("Cluster"),
+        Cell::new("Offset"),
+        Cell::new("Length"),
+        Cell::new("All zero"),
+    ]));
+    for s in slack {
+        t.add_row(Row::new(vec![
+            Cell::new(&format!("0x{:016x}", s.inode)),
+            Cell::new(&s.path),
+            Cell::new(&s.cluster.to_string()),
+            Cell::new(&format!("0x{:x}", s.volume_offset)),
+            Cell::new(&s.length.to_string()),
+            Cell::new(&s.all_zero.to_string()),
+        ]));
+    }
+    t
+}
+
+/// Slack of one file, or `None` if its data ends on a cluster boundary (or it is empty).
+fn slack_of<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    inode: u64,
+    path: String,
+    fr: &FileRecord,
+) -> Result<Option<FileSlack>, FsError> {
+    let bpc = fs.bpb.bytes_per_cluster();
+    let used = fr.size % bpc;
+    if fr.is_dir() || fr.first_cluster < 2 || fr.size == 0 || used == 0 {
+        return Ok(None);
+    }
+    let n = fr.size.div_ceil(bpc);
+    let cluster = if fr.general_flags & 0x02 != 0 {
+        fr.first_cluster.saturating_add((n - 1) as u32)
+    } else {
+        let chain = fs.fat().walk_chain(fr.first_cluster, n as usize)?;
+        if (chain.len() as u64) < n {
+            warn!(
+                "slack: chain of '{}' is {} clusters, {} expected",
+                path,
+                chain.len(),
+                n
+            );
+            return Ok(None);
+        }
+        chain[n as usize - 1]
+    };
+
+    let block = fs.read_cluster(cluster)?;
+    let data = block[used as usize..].to_vec();
+    Ok(Some(FileSlack {
+        inode,
+        path,
+        cluster,
+        volume_offset: fs.cluster_to_offset(cluster) + used,
+        length: data.len() as u64,
+        all_zero: data.iter().all(|&b| b == 0),
+        data,
+    }))
+}
+
+/// Slack of the file with fake inode number `inode`.
+pub fn file_slack<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    inode: u64,
+) -> Result<Option<FileSlack>, FsError> {
+    let records = fs.indexed_records()?;
+    let (_, _, fr) = records
+        .iter()
+        .find(|(ino, _, _)| *ino == inode)
+        .ok_or_else(|| FsError::NotFound(format!("inode 0x{:016x}", inode)))?;
+    let path = fs.path_of(inode)?;
+    slack_of(fs, inode, path, fr)
+}
+
+/// Visit the slack of every allocated file, one file at a time, in inode order.
+pub fn for_each_slack<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    mut visit: impl FnMut(FileSlack) -> Result<(), FsError>,
+) -> Result<usize, FsError> {
+    let records = fs.indexed_records()?;
+    let mut paths = fs.indexed_paths()?;
+    let mut count = 0usize;
+    for (ino, _, fr) in &records {
+        let path = paths.remove(ino).unwrap_or_default();
+        if let Some(s) = slack_of(fs, *ino, path, fr)? {
+            visit(s)?;
+            count += 1;
+        }
+    }
+    debug!("slack: {} files with slack", count);
+    Ok(count)
+}
@@ -0,0 +1,101 @@
+use crate::fs::{ExFatFS, FsError};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{Read, Seek, Write};
+
+/// One contiguous run of unallocated clusters in the exported stream.
+/// Any output offset `o` in `output_offset..output_offset + length` maps to
+/// cluster `first_cluster + (o - output_offset) / bytes_per_cluster`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnallocRun {
+    pub output_offset: u64,
+    pub first_cluster: u32,
+    pub cluster_count: u32,
+    pub volume_offset: u64, // bytes from the start of the volume
+    pub body_offset: u64,   // bytes from the start of the body (volume offset + partition offset)
+    pub length: u64,
+}
+
+/// Summary of an unallocated space export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnallocExport {
+    pub bytes_per_cluster: u64,
+    pub clusters: u64,
+    pub bytes: u64,
+    pub runs: usize,
+}
+
+impl UnallocExport {
+    pub fn to_json(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_else(|_| json!({}))
+    }
+}
+
+/// Header line of the CSV offset map written by [`export_unallocated`].
+pub const UNALLOC_MAP_HEADER: &str =
+    "output_offset,first_cluster,cluster_count,volume_offset,body_offset,length";
+
+/// Concatenate every unallocated cluster (per the active allocation bitmap) into `out`
+/// and write one CSV line per contiguous run to `map`. Clusters are read and written one
+/// at a time. `partition_offset` is the volume's byte offset in the body.
+pub fn export_unallocated<T: Read + Seek, W: Write, M: Write>(
+    fs: &mut ExFatFS<T>,
+    partition_offset: u64,
+    out: &mut W,
+    map: &mut M,
+) -> Result<UnallocExport, FsError> {
+    let bitmap = fs.allocation_bitmap()?;
+    let bpc = fs.bpb.bytes_per_cluster();
+    writeln!(map, "{}", UNALLOC_MAP_HEADER)?;
+
+    let mut output_offset = 0u64;
+    let mut clusters = 0u64;
+    let mut runs = 0usize;
+    for r in bitmap.unallocated_runs() {
+        let volume_offset = fs.cluster_to_offset(r.start);
+        let run = UnallocRun {
+            output_offset,
+            first_cluster: r.start,
+            cluster_count: r.end - r.start,
+            volume_offset,
+            body_offset: partition_offset + volume_offset,
+            length: (r.end - r.start) as u64 * bpc,
+        };
+        for cl in r {
+            out.write_all(&fs.read_cluster(cl)?)?;
+        }
+        writeln!(
+            map,
+            "{},{},{},{},{},{}",
+            run.output_offset,
+            run.first_cluster,
+            run.cluster_count,
+            run.volume_offset,
+            run.body_offset,
+            run.length
+        )?;
+        debug!(
+            "unalloc: clusters {}..{} -> 0x{:x}",
+            run.first_cluster,
+            run.first_cluster + run.cluster_count,
+            run.output_offset
+        );
+        output_offset += run.length;
+        clusters += run.cluster_count as u64;
+        runs += 1;
+    }
+    out.flush()?;
+    map.flush()?;
+    info!(
+        "exported {} unallocated clusters ({} bytes) in {} runs",
+        clusters, output_offset, runs
+    );
+
+    Ok(UnallocExport {
+        bytes_per_cluster: bpc,
+        clusters,
+        bytes: output_offset,
+        runs,
+    })
+}
@@ -0,0 +1,180 @@
+use prettytable::{Cell, Row, Table};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cmp::Ordering;
+
+/// TableChecksum of the recommended up-case table of the exFAT specification
+/// (5836 bytes compressed), written by Windows and by mkfs.exfat.
+pub const STANDARD_UPCASE_CHECKSUM: u32 = 0xE619_D30D;
+pub const STANDARD_UPCASE_LENGTH: u64 = 5836;
+
+/// TableChecksum: rotate right by one, add the next byte, over the whole table.
+pub fn upcase_checksum(data: &[u8]) -> u32 {
+    data.iter()
+        .fold(0u32, |s, &b| s.rotate_right(1).wrapping_add(b as u32))
+}
+
+/// The volume's up-case table, expanded to one mapping per UTF-16 code unit.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpcaseTable {
+    pub length: u64, // DataLength of the on-disk (possibly compressed) table
+    pub stored_checksum: u32,
+    pub computed_checksum: u32,
+    pub valid_checksum: bool,
+    pub compressed: bool,
+    pub mapped_units: usize, // code units that do not map to themselves
+    /// Checksum and length match the specification's recommended table.
+    pub standard: bool,
+    #[serde(skip)]
+    map: Vec<u16>,
+}
+
+impl UpcaseTable {
+    /// Expand an on-disk table. 0xFFFF followed by N is a run of N identity mappings;
+    /// units past the end of the table map to themselves.
+    pub fn from_bytes(data: &[u8], stored_checksum: u32) -> Self {
+        let units: Vec<u16> = data
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        let mut map: Vec<u16> = Vec::with_capacity(0x10000);
+        let mut compressed = false;
+        let mut i = 0;
+        while i < units.len() && map.len() < 0x10000 {
+            if units[i] == 0xFFFF && i + 1 < units.len() {
+                compressed = true;
+                let run = units[i + 1] as usize;
+                let start = map.len();
+                let end = (start + run).min(0x10000);
+                map.extend((start..end).map(|u| u as u16));
+                i += 2;
+            } else {
+                map.push(units[i]);
+                i += 1;
+            }
+        }
+        let start = map.len();
+        map.extend((start..0x10000).map(|u| u as u16));
+
+        let computed_checksum = upcase_checksum(data);
+        let mapped_units = map
+            .iter()
+            .enumerate()
+            .filter(|(u, m)| *u != **m as usize)
+            .count();
+        Self {
+            length: data.len() as u64,
+            stored_checksum,
+            computed_checksum,
+            valid_checksum: computed_checksum == stored_checksum,
+            compressed,
+            mapped_units,
+            standard: computed_checksum == STANDARD_UPCASE_CHECKSUM
+                && data.len() as u64 == STANDARD_UPCASE_LENGTH,
+            map,
+        }
+    }
+
+    /// Minimal table (ASCII a-z only), used when the volume's table cannot be read.
+    pub fn ascii() -> Self {
+        let map: Vec<u16> = (0..=0xFFFFu32)
+            .map(|u| match u {
+                0x61..=0x7A => (u - 0x20) as u16,
+                _ => u as u16,
+            })
+            .collect();
+        Self {
+            length: 0,
+            stored_checksum: 0,
+            computed_checksum: 0,
+            valid_checksum: false,
+            compressed: false,
+            mapped_units: 26,
+            standard: false,
+            map,
+        }
+    }
+
+    #[inline]
+    pub fn upcase(&self, unit: u16) -> u16 {
+        self.map[unit as usize]
+    }
+
+    /// Up-cased UTF-16 code units of `name`.
+    pub fn upcase_name(&self, name: &str) -> Vec<u16> {
+        name.encode_utf16().map(|u| self.upcase(u)).collect()
+    }
+
+    /// Case-insensitive comparison as exFAT does it (per UTF-16 code unit).
+    pub fn cmp_names(&self, a: &str, b: &str) -> Ordering {
+        a.encode_utf16()
+            .map(|u| self.upcase(u))
+            .cmp(b.encode_utf16().map(|u| self.upcase(u)))
+    }
+
+    pub fn names_equal(&self, a: &str, b: &str) -> bool {
+        self.cmp_names(a, b) == Ordering::Equal
+    }
+
+    /// NameHash of the Stream Extension entry: 16-bit rotate-and-add over the bytes
+    /// of the up-cased name.
+    pub fn name_hash(&self, name: &str) -> u16 {
+        self.name_hash_units(&name.encode_utf16().collect::<Vec<_>>())
+    }
+
+    /// NameHash over raw UTF-16 code units (names may hold unpaired surrogates).
+    pub fn name_hash_units(&self, units: &[u16]) -> u16 {
+        units.iter().map(|&u| self.upcase(u)).fold(0u16, |h, u| {
+            let h = h.rotate_right(1).wrapping_add(u & 0xFF);
+            h.rotate_right(1).wrapping_add(u >> 8)
+        })
+    }
+
+    /// Findings worth reporting (empty for a sound, standard table).
+    pub fn findings(&self) -> Vec<String> {
+        let mut out = Vec::new();
+        if !self.valid_checksum {
+            out.push(format!(
+                "up-case TableChecksum mismatch: stored 0x{:08X}, computed 0x{:08X}",
+                self.stored_checksum, self.computed_checksum
+            ));
+        }
+        if !self.standard {
+            out.push(format!(
+                "non-standard up-case table ({} bytes, checksum 0x{:08X}, {} mapped code units)",
+                self.length, self.computed_checksum, self.mapped_units
+            ));
+        }
+        out
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut v = serde_json::to_value(self).unwrap_or_else(|_| json!({}));
+        v["findings"] = json!(self.findings());
+        v
+    }
+}
+
+impl std::fmt::Display for UpcaseTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut t = Table::new();
+        for (k, v) in [
+            ("Length", format!("{} bytes", self.length)),
+            ("Compressed", self.compressed.to_string()),
+            ("Stored checksum", format!("0x{:08X}", self.stored_checksum)),
+            (
+                "Computed checksum",
+                format!("0x{:08X}", self.computed_checksum),
+            ),
+            ("Checksum valid", self.valid_checksum.to_string()),
+            ("Mapped code units", self.mapped_units.to_string()),
+            ("Standard table", self.standard.to_string()),
+        ] {
+            t.add_row(Row::new(vec![Cell::new(k), Cell::new(&v)]));
+        }
+        for msg in self.findings() {
+            t.add_row(Row::new(vec![Cell::new("Finding"), Cell::new(&msg)]));
+        }
+        write!(f, "{}", t)
+    }
+}
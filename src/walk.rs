@@ -0,0 +1,155 @@
+use crate::direntry::{EntryType, FileRecord, RawDirEnt, assemble_file};
+use crate::fs::{ExFatFS, FsError};
+use log::{debug, warn};
+use std::collections::HashSet;
+use std::io::{Read, Seek};
+
+/// What to do when a directory cannot be read during a walk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop and return the error.
+    #[default]
+    Abort,
+    /// Log it, record it in `WalkStats::errors` and go on with the next entry.
+    Continue,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkOptions {
+    /// Deepest level visited (entries of the root are depth 0); `None` for no limit.
+    pub max_depth: Option<usize>,
+    /// Also visit deleted entry sets (InUse cleared). Deleted directories are not descended into.
+    pub include_deleted: bool,
+    pub on_error: ErrorPolicy,
+}
+
+/// An entry set met during a walk.
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: String,
+    pub inode: u64, // (parent dir first cluster << 32) | entry index, as in the fake-inode index
+    pub record: FileRecord,
+    pub depth: usize,
+    pub deleted: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct WalkStats {
+    pub visited: usize,
+    /// Entry sets that could not be decoded (always skipped), and directories that
+    /// could not be read under `ErrorPolicy::Continue`.
+    pub errors: Vec<String>,
+}
+
+/// A directory being walked: its entries and the position of the next one.
+struct Frame {
+    cluster: u32,
+    path: String,
+    depth: usize,
+    ents: Vec<RawDirEnt>,
+    next: usize,
+}
+
+/// Depth-first, pre-order walk from the root: every directory's entries in on-disk
+/// order, each directory followed by its contents. Uses an explicit stack, so deeply
+/// nested (or crafted) trees cannot overflow the call stack. Errors of `visit` always abort.
+pub fn walk<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    opts: &WalkOptions,
+    mut visit: impl FnMut(WalkEntry) -> Result<(), FsError>,
+) -> Result<WalkStats, FsError> {
+    let mut stats = WalkStats::default();
+    let mut seen = HashSet::new();
+    let mut stack = Vec::new();
+    let root = fs.root_dir_record();
+    if let Some(f) = open_dir(fs, &root, String::new(), 0, opts, &mut stats, &mut seen)? {
+        stack.push(f);
+    }
+
+    while let Some(frame) = stack.last_mut() {
+        let i = frame.next;
+        if i >= frame.ents.len() || frame.ents[i].kind() == EntryType::End {
+            stack.pop();
+            continue;
+        }
+        let deleted = !frame.ents[i].is_active();
+        if frame.ents[i].kind_normalized() != EntryType::File || (deleted && !opts.include_deleted)
+        {
+            frame.next += 1;
+            continue;
+        }
+        let sec_cnt = frame.ents[i].raw[1] as usize;
+        let end = (i + 1 + sec_cnt).min(frame.ents.len());
+        frame.next = end;
+        let Some(fr) = assemble_file(&frame.ents[i..end]) else {
+            let msg = format!(
+                "{}: undecodable entry set (dir_cluster={}, i={})",
+                frame.path, frame.cluster, i
+            );
+            warn!("walk: {}", msg);
+            stats.errors.push(msg);
+            continue;
+        };
+        let path = format!("{}/{}", frame.path, fr.name);
+        let inode = ((frame.cluster as u64) << 32) | i as u64;
+        let depth = frame.depth;
+        let descend = !deleted
+            && fr.is_dir()
+            && fr.first_cluster >= 2
+            && opts.max_depth.is_none_or(|max| depth < max);
+        visit(WalkEntry {
+            path: path.clone(),
+            inode,
+            record: fr.clone(),
+            depth,
+            deleted,
+        })?;
+        stats.visited += 1;
+        if descend && let Some(f) = open_dir(fs, &fr, path, depth + 1, opts, &mut stats, &mut seen)?
+        {
+            stack.push(f);
+        }
+    }
+    debug!(
+        "walk: {} entries, {} errors",
+        stats.visited,
+        stats.errors.len()
+    );
+    Ok(stats)
+}
+
+/// Read directory `dir` into a frame; `None` if it was already walked or, under
+/// `ErrorPolicy::Continue`, could not be read.
+fn open_dir<T: Read + Seek>(
+    fs: &mut ExFatFS<T>,
+    dir: &FileRecord,
+    path: String,
+    depth: usize,
+    opts: &WalkOptions,
+    stats: &mut WalkStats,
+    seen: &mut HashSet<u32>,
+) -> Result<Option<Frame>, FsError> {
+    // a corrupted tree may link a directory into itself
+    if !seen.insert(dir.first_cluster) {
+        warn!(
+            "walk: directory cluster {} already visited ('{}'), skipping",
+            dir.first_cluster, path
+        );
+        return Ok(None);
+    }
+    match fs.read_dir_entries(dir) {
+        Ok(ents) => Ok(Some(Frame {
+            cluster: dir.first_cluster,
+            path,
+            depth,
+            ents,
+            next: 0,
+        })),
+        Err(e) if opts.on_error == ErrorPolicy::Continue => {
+            warn!("walk: cannot read '{}': {}", path, e);
+            stats.errors.push(format!("{}: {}", path, e));
+            Ok(None)
+        }
+        Err(e) => Err(e),
+    }
+}